@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use futures::StreamExt;
+
+use crate::chat;
+use crate::config::Config;
+use crate::db::{Db, MemoryFilter};
+use crate::embeddings::EmbeddingClient;
+use crate::models::{AskRequest, AskResponse, SearchQuery, SearchResult};
+use crate::search;
+use crate::sse::{self, Event, Events};
+
+/// Memories retrieved for a question unless the request says otherwise
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+
+pub const REFUSAL: &str = "I don't have any memories relevant enough to answer that.";
+
+/// Answer a question from the memories most similar to it, citing the ones
+/// the answer relies on. When none reaches the minimum score the question
+/// is refused without asking the chat model, so it can't make something up.
+pub async fn ask(
+    db: &Db,
+    config: &Config,
+    embeddings: &EmbeddingClient,
+    collection: &str,
+    req: &AskRequest,
+    events: Option<&Events>,
+) -> Result<AskResponse> {
+    let memories = retrieve(db, config, embeddings, collection, req).await?;
+    if memories.is_empty() {
+        return Ok(AskResponse {
+            answer: REFUSAL.to_string(),
+            citations: Vec::new(),
+            refused: true,
+        });
+    }
+
+    let prompt = prompt(&req.question, &memories);
+    let mut citations = Citations::new(memories);
+    let mut answer = match events {
+        None => {
+            let answer = chat::generate(config, prompt)
+                .await
+                .context("Answer generation failed")?;
+            citations.push(&answer)
+        }
+        Some(events) => {
+            let mut stream = chat::generate_stream(config, prompt)
+                .await
+                .context("Answer generation failed")?;
+            let mut answer = String::new();
+            while let Some(token) = stream.next().await {
+                let text = citations.push(&token.context("Answer generation failed")?);
+                if !text.is_empty() {
+                    answer.push_str(&text);
+                    sse::send(events, Event::Token(text)).await?;
+                }
+            }
+            answer
+        }
+    };
+
+    let (rest, citations) = citations.finish();
+    if !rest.is_empty() {
+        if let Some(events) = events {
+            sse::send(events, Event::Token(rest.clone())).await?;
+        }
+        answer.push_str(&rest);
+    }
+
+    Ok(AskResponse {
+        answer: answer.trim().to_string(),
+        citations,
+        refused: false,
+    })
+}
+
+/// The memories a question is answered from, most similar first: only
+/// vector scores are compared with the threshold, since they mean the same
+/// thing for every question.
+async fn retrieve(
+    db: &Db,
+    config: &Config,
+    embeddings: &EmbeddingClient,
+    collection: &str,
+    req: &AskRequest,
+) -> Result<Vec<SearchResult>> {
+    let query = SearchQuery {
+        q: req.question.clone(),
+        limit: Some(req.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)),
+        tags: (!req.tags.is_empty()).then(|| req.tags.join(",")),
+        source: req.source.clone(),
+        min_score: Some(req.min_score.unwrap_or(config.ask_min_score)),
+        ..Default::default()
+    };
+    let filter = MemoryFilter {
+        collection: Some(collection.to_string()),
+        ..search::filter_from_query(&query)?
+    };
+    search::search(db, embeddings, &query, &filter).await
+}
+
+fn prompt(question: &str, memories: &[SearchResult]) -> String {
+    let notes = memories
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let created = &r.memory.created_at;
+            format!("[{}] ({}) {}", i + 1, created.get(..10).unwrap_or(created), r.memory.text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "Answer the question using only the numbered notes below, which were written by the person asking. \
+         Cite every note you rely on by its number in square brackets, like [2]. \
+         If the notes don't answer the question, say that you don't know instead of guessing.\n\n\
+         Notes:\n{}\n\n\
+         Question: {}\n\n\
+         Answer:",
+        notes, question
+    )
+}
+
+/// Longest bracketed text held back while looking for a citation
+const MAX_CITATION_LEN: usize = 64;
+
+/// Rewrites the note numbers the model cites, like `[2]` or `[1, 3]`, to
+/// memory ids as the answer is written. Text that may still turn into a
+/// citation is held back until it's clear; brackets that aren't a list of
+/// valid note numbers are left alone.
+struct Citations {
+    memories: Vec<Option<SearchResult>>,
+    /// Indexes of the cited notes, in order of first citation
+    cited: Vec<usize>,
+    pending: String,
+}
+
+impl Citations {
+    fn new(memories: Vec<SearchResult>) -> Self {
+        Self {
+            memories: memories.into_iter().map(Some).collect(),
+            cited: Vec::new(),
+            pending: String::new(),
+        }
+    }
+
+    /// Take the next piece of the answer and return the text that's ready.
+    fn push(&mut self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c == '[' {
+                out.push_str(&std::mem::take(&mut self.pending));
+            } else if self.pending.is_empty() {
+                out.push(c);
+                continue;
+            }
+
+            self.pending.push(c);
+            if c == ']' {
+                let pending = std::mem::take(&mut self.pending);
+                out.push_str(&self.resolve(&pending).unwrap_or(pending));
+            } else if c == '\n' || self.pending.len() > MAX_CITATION_LEN {
+                out.push_str(&std::mem::take(&mut self.pending));
+            }
+        }
+        out
+    }
+
+    /// `[1, 3]` as `[<id>, <id>]`, or `None` if it isn't a citation.
+    fn resolve(&mut self, bracketed: &str) -> Option<String> {
+        let count = self.memories.len();
+        let numbers: Vec<usize> = bracketed[1..bracketed.len() - 1]
+            .split(',')
+            .map(|n| n.trim().parse().ok().filter(|n| (1..=count).contains(n)))
+            .collect::<Option<_>>()?;
+
+        let mut ids = Vec::with_capacity(numbers.len());
+        for n in numbers {
+            if !self.cited.contains(&(n - 1)) {
+                self.cited.push(n - 1);
+            }
+            ids.push(self.memories[n - 1].as_ref().map_or("", |r| r.memory.id.as_str()));
+        }
+        Some(format!("[{}]", ids.join(", ")))
+    }
+
+    /// The text still held back, and the cited memories.
+    fn finish(mut self) -> (String, Vec<SearchResult>) {
+        let cited = self.cited.iter().filter_map(|&i| self.memories[i].take()).collect();
+        (self.pending, cited)
+    }
+}
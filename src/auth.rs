@@ -0,0 +1,323 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db::DEFAULT_COLLECTION;
+use crate::models::ApiResponse;
+use crate::server::SharedState;
+
+/// What a key may do. Each scope includes the ones below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyScope {
+    /// Get, list and search memories; stats and profiles
+    Read,
+    /// Create, edit and delete memories
+    Write,
+    /// Manage collections and run admin jobs
+    Admin,
+}
+
+impl std::fmt::Display for KeyScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Admin => "admin",
+        })
+    }
+}
+
+impl std::str::FromStr for KeyScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            "admin" => Ok(Self::Admin),
+            other => anyhow::bail!("Unknown scope '{}' (expected read, write or admin)", other),
+        }
+    }
+}
+
+/// An API key as stored on disk. Only a SHA-256 hash of the secret is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    /// First characters of the secret, to help recognise a key
+    pub prefix: String,
+    pub hash: String,
+    pub scopes: Vec<KeyScope>,
+    /// Collections the key may access; `None` means all of them
+    pub collections: Option<Vec<String>>,
+    pub created_at: String,
+}
+
+impl ApiKey {
+    fn allows(&self, scope: KeyScope) -> bool {
+        self.scopes.iter().any(|s| *s >= scope)
+    }
+
+    fn allows_collection(&self, collection: &str) -> bool {
+        self.collections
+            .as_ref()
+            .is_none_or(|c| c.iter().any(|name| name == collection))
+    }
+}
+
+/// The keys file in the data directory. The server re-reads it whenever it
+/// changes, so `memorai keys` takes effect without a restart.
+#[derive(Clone)]
+pub struct ApiKeys {
+    path: PathBuf,
+    cache: Arc<Mutex<(Option<SystemTime>, Vec<ApiKey>)>>,
+}
+
+impl ApiKeys {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join("api_keys.json"),
+            cache: Arc::default(),
+        }
+    }
+
+    /// Keys currently on disk, reloading the file if it was modified.
+    pub fn keys(&self) -> Result<Vec<ApiKey>> {
+        let modified = match std::fs::metadata(&self.path) {
+            Ok(meta) => Some(meta.modified()?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err).context("Failed to read API keys file"),
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.0 != modified {
+            *cache = (modified, read_keys(&self.path)?);
+        }
+        Ok(cache.1.clone())
+    }
+
+    /// Add a key and return it along with its secret, which isn't stored.
+    pub fn create(
+        &self,
+        name: &str,
+        scopes: Vec<KeyScope>,
+        collections: Option<Vec<String>>,
+    ) -> Result<(ApiKey, String)> {
+        let secret = format!("mk_{}", uuid::Uuid::new_v4().simple());
+        let key = ApiKey {
+            id: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            name: name.to_string(),
+            prefix: secret[..7].to_string(),
+            hash: hash_secret(&secret),
+            scopes,
+            collections,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let mut keys = read_keys(&self.path)?;
+        keys.push(key.clone());
+        write_keys(&self.path, &keys)?;
+        Ok((key, secret))
+    }
+
+    /// Remove a key by id, returning it if it existed.
+    pub fn revoke(&self, id: &str) -> Result<Option<ApiKey>> {
+        let mut keys = read_keys(&self.path)?;
+        let Some(index) = keys.iter().position(|k| k.id == id) else {
+            return Ok(None);
+        };
+
+        let key = keys.remove(index);
+        write_keys(&self.path, &keys)?;
+        Ok(Some(key))
+    }
+}
+
+/// Who made a request: the name of its API key, or `None` while the API is
+/// unauthenticated. Recorded in the history of the memories it changes.
+#[derive(Debug, Clone, Default)]
+pub struct Actor(pub Option<String>);
+
+impl FromRequest for Actor {
+    type Error = std::convert::Infallible;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        std::future::ready(Ok(req.extensions().get::<Actor>().cloned().unwrap_or_default()))
+    }
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+fn read_keys(path: &Path) -> Result<Vec<ApiKey>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Write via a temporary file so the server never sees a half-written file.
+fn write_keys(path: &Path, keys: &[ApiKey]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create data directory")?;
+    }
+
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(keys)?)
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// The highest scope of a request's API key, or `None` while the API is
+/// unauthenticated. Lets a handler check more than its route needs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Access(pub Option<KeyScope>);
+
+impl Access {
+    pub fn allows(self, scope: KeyScope) -> bool {
+        !matches!(self.0, Some(s) if s < scope)
+    }
+}
+
+impl FromRequest for Access {
+    type Error = std::convert::Infallible;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        std::future::ready(Ok(req.extensions().get::<Access>().copied().unwrap_or_default()))
+    }
+}
+
+/// What a request needs from its key: a scope, and the collection it touches
+/// (`None` for routes that aren't about a single collection).
+fn requirements(method: &Method, path: &str) -> (KeyScope, Option<String>) {
+    let segments: Vec<&str> = path
+        .trim_start_matches("/v1")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    // Asking a question only reads memories, even though it's a POST, and
+    // MCP checks the write scope per tool
+    let reads = *method == Method::POST && matches!(segments.last(), Some(&"ask" | &"mcp"));
+    let by_method = if matches!(*method, Method::GET | Method::HEAD) || reads {
+        KeyScope::Read
+    } else {
+        KeyScope::Write
+    };
+
+    // Purging the trash can't be undone, so it takes more than a write key
+    let purge = *method == Method::DELETE;
+
+    match segments.as_slice() {
+        ["collections"] if by_method == KeyScope::Read => (KeyScope::Read, None),
+        ["collections"] => (KeyScope::Admin, None),
+        ["collections", name] => (KeyScope::Admin, Some(decode(name))),
+        ["collections", name, "trash"] if purge => (KeyScope::Admin, Some(decode(name))),
+        ["collections", name, ..] => (by_method, Some(decode(name))),
+        ["admin", ..] | ["jobs", ..] => (KeyScope::Admin, None),
+        ["trash"] if purge => (KeyScope::Admin, Some(DEFAULT_COLLECTION.to_string())),
+        _ => (by_method, Some(DEFAULT_COLLECTION.to_string())),
+    }
+}
+
+fn decode(segment: &str) -> String {
+    urlencoding::decode(segment)
+        .map(|s| s.into_owned())
+        .unwrap_or_else(|_| segment.to_string())
+}
+
+/// Bearer-token authentication for the `/v1` API. Requests are let through
+/// unchecked while no keys exist, so a fresh install keeps working.
+pub async fn require_api_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let keys = match req.app_data::<SharedState>() {
+        Some(state) => state.read().await.keys.keys(),
+        None => Err(anyhow::anyhow!("Missing app state")),
+    };
+
+    let keys = match keys {
+        Ok(keys) => keys,
+        Err(err) => {
+            tracing::error!("Failed to load API keys: {:#}", err);
+            let response = HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error("Failed to load API keys"));
+            return Ok(req.into_response(response));
+        }
+    };
+
+    if keys.is_empty() {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let secret = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    let Some(key) = secret.and_then(|s| {
+        let hash = hash_secret(s);
+        keys.iter().find(|k| k.hash == hash)
+    }) else {
+        let response = HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .json(ApiResponse::<()>::error("Missing or invalid API key"));
+        return Ok(req.into_response(response));
+    };
+
+    let (scope, collection) = requirements(req.method(), req.path());
+
+    if !key.allows(scope) {
+        let response = HttpResponse::Forbidden().json(ApiResponse::<()>::error(format!(
+            "API key '{}' lacks the {} scope",
+            key.name, scope
+        )));
+        return Ok(req.into_response(response));
+    }
+
+    let allowed = match &collection {
+        Some(collection) => key.allows_collection(collection),
+        None => key.collections.is_none(),
+    };
+    if !allowed {
+        let response = HttpResponse::Forbidden().json(ApiResponse::<()>::error(format!(
+            "API key '{}' is not allowed to access {}",
+            key.name,
+            collection
+                .map(|c| format!("collection '{}'", c))
+                .unwrap_or_else(|| "this route".to_string())
+        )));
+        return Ok(req.into_response(response));
+    }
+
+    req.extensions_mut().insert(Actor(Some(key.name.clone())));
+    req.extensions_mut().insert(Access(key.scopes.iter().max().copied()));
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+
+use crate::chat;
+use crate::config::Config;
+use crate::db::{self, Db};
+use crate::models::CreateMemoryRequest;
+
+/// Most used tags of a collection offered to the model
+const MAX_VOCABULARY: usize = 200;
+/// Tags kept from one suggestion
+const MAX_TAGS: usize = 5;
+/// Of those, tags the collection doesn't use yet
+const MAX_NEW_TAGS: usize = 2;
+const MAX_TAG_LEN: usize = 32;
+/// Characters of the memory shown to the model
+const MAX_TEXT_CHARS: usize = 4000;
+
+/// Tags the memories of one request with the chat model. The collection's
+/// tags are loaded the first time they're needed and new tags are added to
+/// them as they're suggested, so later memories in a bulk request can reuse
+/// them.
+pub struct Tagger<'a> {
+    db: &'a Db,
+    config: &'a Config,
+    collection: &'a str,
+    vocabulary: Option<Vec<String>>,
+}
+
+impl<'a> Tagger<'a> {
+    pub fn new(db: &'a Db, config: &'a Config, collection: &'a str) -> Self {
+        Self {
+            db,
+            config,
+            collection,
+            vocabulary: None,
+        }
+    }
+
+    /// Add the chat model's tags to `req`, recording them in `auto_tags`.
+    /// Memories are tagged when the request says so, or else when they come
+    /// without tags and `auto_tag` is on. Tagging is best effort: if it fails
+    /// the memory keeps the tags it came with.
+    pub async fn tag(&mut self, req: &mut CreateMemoryRequest) {
+        if !req.auto_tag.unwrap_or(self.config.auto_tag && req.tags.is_empty()) {
+            return;
+        }
+        if let Err(err) = self.try_tag(req).await {
+            tracing::warn!("Auto-tagging failed, storing memory with its own tags: {:#}", err);
+        }
+    }
+
+    async fn try_tag(&mut self, req: &mut CreateMemoryRequest) -> Result<()> {
+        let vocabulary = match &mut self.vocabulary {
+            Some(vocabulary) => vocabulary,
+            None => {
+                let tags = db::get_top_tags(self.db, self.collection, MAX_VOCABULARY)
+                    .await
+                    .context("Failed to load tags")?;
+                self.vocabulary.insert(tags)
+            }
+        };
+
+        let reply = chat::generate(self.config, prompt(&req.text, vocabulary))
+            .await
+            .context("Tag generation failed")?;
+
+        for tag in parse(&reply, vocabulary) {
+            if req.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                continue;
+            }
+            if !vocabulary.contains(&tag) {
+                vocabulary.push(tag.clone());
+            }
+            req.tags.push(tag.clone());
+            req.auto_tags.push(tag);
+        }
+        Ok(())
+    }
+}
+
+fn prompt(text: &str, vocabulary: &[String]) -> String {
+    let existing = if vocabulary.is_empty() {
+        "(none yet)".to_string()
+    } else {
+        vocabulary.join(", ")
+    };
+    let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+
+    format!(
+        "Suggest up to {} short tags for the note below, so it can be found with related notes. \
+         Use tags from the existing list whenever one fits, and only make up a new tag \
+         (lowercase, words joined by hyphens) when none does. \
+         Reply with the tags separated by commas and nothing else.\n\n\
+         Existing tags: {}\n\n\
+         Note: {}\n\n\
+         Tags:",
+        MAX_TAGS, existing, text
+    )
+}
+
+/// The tags in the model's reply, spelled as in `vocabulary` where they
+/// match one; at most `MAX_NEW_TAGS` of them are new.
+fn parse(reply: &str, vocabulary: &[String]) -> Vec<String> {
+    // Models like to repeat the label from the prompt
+    let reply = match reply.split_once(':') {
+        Some((label, rest)) if label.trim().eq_ignore_ascii_case("tags") => rest,
+        _ => reply,
+    };
+
+    let mut tags: Vec<String> = Vec::new();
+    let mut new = 0;
+    for raw in reply.split([',', '\n']) {
+        let tag = normalize(raw);
+        if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+            continue;
+        }
+
+        let tag = match vocabulary.iter().find(|t| t.eq_ignore_ascii_case(&tag)) {
+            Some(existing) => existing.clone(),
+            None => tag,
+        };
+        if tags.contains(&tag) {
+            continue;
+        }
+        if !vocabulary.contains(&tag) {
+            if new == MAX_NEW_TAGS {
+                continue;
+            }
+            new += 1;
+        }
+
+        tags.push(tag);
+        if tags.len() == MAX_TAGS {
+            break;
+        }
+    }
+    tags
+}
+
+/// `"#Machine Learning"` as `machine-learning`
+fn normalize(raw: &str) -> String {
+    raw.trim_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '#' | '*' | '-' | '.' | '[' | ']'))
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+}
@@ -0,0 +1,113 @@
+use actix_web::web::Bytes;
+use anyhow::{Context, Result};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::Client;
+
+use crate::config::Config;
+use crate::models::{OllamaGenerateRequest, OllamaGenerateResponse};
+
+/// Complete `prompt` with the configured chat model through Ollama's
+/// `/api/generate` endpoint.
+pub async fn generate(config: &Config, prompt: String) -> Result<String> {
+    let response = send(config, prompt, false).await?;
+
+    let gen_response: OllamaGenerateResponse = response
+        .json()
+        .await
+        .context("Failed to parse Ollama generate response")?;
+
+    Ok(gen_response.response.trim().to_string())
+}
+
+/// Like `generate`, but yields the completion piece by piece as the model
+/// writes it, read from Ollama's NDJSON stream.
+pub async fn generate_stream(config: &Config, prompt: String) -> Result<BoxStream<'static, Result<String>>> {
+    let response = send(config, prompt, true).await?;
+
+    let state = NdjsonStream {
+        body: Some(response.bytes_stream().boxed()),
+        buffer: Vec::new(),
+        done: false,
+    };
+    Ok(futures::stream::unfold(state, NdjsonStream::next).boxed())
+}
+
+async fn send(config: &Config, prompt: String, stream: bool) -> Result<reqwest::Response> {
+    let client = Client::new();
+    let url = format!("{}/api/generate", config.ollama_url);
+    let request = OllamaGenerateRequest {
+        model: config.chat_model.clone(),
+        prompt,
+        stream,
+    };
+
+    let response = client
+        .post(&url)
+        .json(&request)
+        .send()
+        .await
+        .context("Failed to connect to Ollama")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Ollama generate request failed ({}): {}", status, body);
+    }
+    Ok(response)
+}
+
+/// Splits a streamed response body into lines, each a JSON object holding
+/// the next piece of the completion
+struct NdjsonStream {
+    /// `None` once the body has been read to the end
+    body: Option<BoxStream<'static, reqwest::Result<Bytes>>>,
+    buffer: Vec<u8>,
+    /// Ollama said the completion is finished, or the stream failed
+    done: bool,
+}
+
+impl NdjsonStream {
+    async fn next(mut self) -> Option<(Result<String>, Self)> {
+        while !self.done {
+            if let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+
+                let chunk: OllamaGenerateResponse = match serde_json::from_slice(&line) {
+                    Ok(chunk) => chunk,
+                    Err(err) => return self.fail(anyhow::Error::new(err).context("Failed to parse Ollama stream")),
+                };
+                if let Some(error) = chunk.error {
+                    return self.fail(anyhow::anyhow!("Ollama generate request failed: {}", error));
+                }
+                self.done = chunk.done;
+                if !chunk.response.is_empty() {
+                    return Some((Ok(chunk.response), self));
+                }
+                continue;
+            }
+
+            let Some(body) = self.body.as_mut() else {
+                return self.fail(anyhow::anyhow!("Ollama stream ended before the completion was done"));
+            };
+            match body.next().await {
+                Some(Ok(bytes)) => self.buffer.extend_from_slice(&bytes),
+                Some(Err(err)) => return self.fail(anyhow::Error::new(err).context("Ollama stream failed")),
+                None => {
+                    // The last line may lack its newline
+                    self.body = None;
+                    self.buffer.push(b'\n');
+                }
+            }
+        }
+        None
+    }
+
+    fn fail(mut self, err: anyhow::Error) -> Option<(Result<String>, Self)> {
+        self.done = true;
+        Some((Err(err), self))
+    }
+}
@@ -1,32 +1,362 @@
+use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use crate::dedup::{DedupPolicy, DedupSettings};
+use crate::profile::Strategy;
 
 #[derive(Clone, Debug)]
 pub struct Config {
+    /// Address the API server binds to
+    pub host: String,
     pub port: u16,
+    /// Also listen on this Unix domain socket
+    pub socket: Option<PathBuf>,
+    /// PEM certificate chain and private key; the server uses HTTPS when set
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Origins allowed to call the API from a browser; `*` allows any
+    pub cors_origins: Vec<String>,
     pub ollama_url: String,
+    /// Embedding backend: `ollama`, `openai`, `local` or `hash`
+    pub embed_provider: String,
+    /// Base URL of an OpenAI-compatible embeddings server
+    pub embed_url: String,
+    pub embed_api_key: Option<String>,
     pub embed_model: String,
+    /// Model directory for the in-process `local` provider
+    pub embed_model_path: Option<PathBuf>,
+    pub embed_dim: usize,
+    /// Texts per embedding request for bulk operations
+    pub embed_batch_size: usize,
+    /// Embedding requests in flight at once for bulk operations
+    pub embed_concurrency: usize,
     pub chat_model: String,
+    /// How profiles pick memories when there are more than `profile_max_memories`
+    pub profile_strategy: Strategy,
+    pub profile_max_memories: usize,
+    /// Tokens of notes sent to the chat model per prompt while building a profile
+    pub profile_token_budget: usize,
+    /// Memories added, edited or removed since the stored profile before it's updated
+    pub profile_refresh_after: usize,
+    /// Similarity to the question a memory needs before `ask` answers from it
+    pub ask_min_score: f32,
+    /// Ask the chat model to tag memories that are written without tags
+    pub auto_tag: bool,
+    /// What to do when a new memory duplicates an existing one
+    pub dedup_policy: DedupPolicy,
+    /// Cosine similarity at which memories count as near-duplicates
+    pub dedup_threshold: f32,
+    /// Days a deleted memory stays in the trash before a purge removes it
+    pub trash_retention_days: u32,
     pub data_dir: PathBuf,
+    /// Server URL for CLI commands, if not derived from host and port
+    pub api_url: Option<String>,
+    /// Key the CLI sends to the API server
+    pub api_key: Option<String>,
+    /// Config file that was read, if any
+    pub config_file: Option<PathBuf>,
+    /// Every setting with its effective value and where it came from
+    pub settings: Vec<Setting>,
+}
+
+/// Where a setting's effective value came from, lowest precedence first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Cli,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::File => "config file",
+            Self::Env => "environment",
+            Self::Cli => "command line",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Setting {
+    pub key: &'static str,
+    /// Display form of the value, with secrets masked
+    pub value: String,
+    pub source: Source,
 }
 
+const EMBED_PROVIDERS: [&str; 4] = ["ollama", "openai", "local", "hash"];
+
+/// Smallest profile token budget, leaving room for more than a few notes per prompt
+pub const MIN_TOKEN_BUDGET: usize = 256;
+
 impl Config {
-    pub fn from_env() -> Self {
-        let home = env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        Self {
-            port: env::var("MEMORAI_PORT")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or(8484),
-            ollama_url: env::var("MEMORAI_OLLAMA_URL")
-                .unwrap_or_else(|_| "http://localhost:11434".to_string()),
-            embed_model: env::var("MEMORAI_EMBED_MODEL")
-                .unwrap_or_else(|_| "mxbai-embed-large".to_string()),
-            chat_model: env::var("MEMORAI_CHAT_MODEL")
-                .unwrap_or_else(|_| "qwen2.5:14b".to_string()),
-            data_dir: env::var("MEMORAI_DATA_DIR")
-                .map(PathBuf::from)
-                .unwrap_or_else(|_| PathBuf::from(home).join(".memorai").join("data")),
+    /// Resolve the configuration from, in increasing precedence: built-in
+    /// defaults, the TOML config file, `MEMORAI_*` environment variables and
+    /// command-line `overrides` (keyed by setting name).
+    ///
+    /// The config file is `path`, else `MEMORAI_CONFIG`, else
+    /// `~/.memorai/config.toml` if it exists.
+    pub fn load(path: Option<&Path>, overrides: HashMap<&'static str, String>) -> Result<Self> {
+        let home = PathBuf::from(env::var("HOME").unwrap_or_else(|_| "/tmp".to_string()));
+
+        let explicit = path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var("MEMORAI_CONFIG").ok().map(PathBuf::from));
+        let config_file = match explicit {
+            Some(path) => Some(path),
+            None => Some(home.join(".memorai").join("config.toml")).filter(|p| p.exists()),
+        };
+
+        let file = match &config_file {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))?;
+                content
+                    .parse::<toml::Table>()
+                    .with_context(|| format!("Failed to parse config file {}", path.display()))?
+            }
+            None => toml::Table::new(),
+        };
+
+        let mut layers = Layers {
+            file,
+            overrides,
+            settings: Vec::new(),
+        };
+
+        let mut config = Self {
+            host: layers.string("host", "MEMORAI_HOST", "127.0.0.1")?,
+            port: layers.parse("port", "MEMORAI_PORT", 8484)?,
+            socket: layers.path("socket", "MEMORAI_SOCKET")?,
+            tls_cert: layers.path("tls_cert", "MEMORAI_TLS_CERT")?,
+            tls_key: layers.path("tls_key", "MEMORAI_TLS_KEY")?,
+            cors_origins: layers.list("cors_origins", "MEMORAI_CORS_ORIGINS")?,
+            ollama_url: layers.string("ollama_url", "MEMORAI_OLLAMA_URL", "http://localhost:11434")?,
+            embed_provider: layers.string("embed_provider", "MEMORAI_EMBED_PROVIDER", "ollama")?,
+            embed_url: layers.string("embed_url", "MEMORAI_EMBED_URL", "http://localhost:8080")?,
+            embed_api_key: layers.secret("embed_api_key", "MEMORAI_EMBED_API_KEY")?,
+            embed_model: layers.string("embed_model", "MEMORAI_EMBED_MODEL", "mxbai-embed-large")?,
+            embed_model_path: layers.path("embed_model_path", "MEMORAI_EMBED_MODEL_PATH")?,
+            embed_dim: layers.parse("embed_dim", "MEMORAI_EMBED_DIM", 1024)?,
+            embed_batch_size: layers.parse("embed_batch_size", "MEMORAI_EMBED_BATCH_SIZE", 32)?,
+            embed_concurrency: layers.parse("embed_concurrency", "MEMORAI_EMBED_CONCURRENCY", 4)?,
+            chat_model: layers.string("chat_model", "MEMORAI_CHAT_MODEL", "qwen2.5:14b")?,
+            profile_strategy: layers.parse("profile_strategy", "MEMORAI_PROFILE_STRATEGY", Strategy::Recent)?,
+            profile_max_memories: layers.parse("profile_max_memories", "MEMORAI_PROFILE_MAX_MEMORIES", 1000)?,
+            profile_token_budget: layers.parse("profile_token_budget", "MEMORAI_PROFILE_TOKEN_BUDGET", 2048)?,
+            profile_refresh_after: layers.parse("profile_refresh_after", "MEMORAI_PROFILE_REFRESH_AFTER", 10)?,
+            ask_min_score: layers.parse("ask_min_score", "MEMORAI_ASK_MIN_SCORE", 0.5)?,
+            auto_tag: layers.parse("auto_tag", "MEMORAI_AUTO_TAG", false)?,
+            dedup_policy: layers.parse("dedup_policy", "MEMORAI_DEDUP_POLICY", DedupPolicy::Merge)?,
+            dedup_threshold: layers.parse("dedup_threshold", "MEMORAI_DEDUP_THRESHOLD", 0.95)?,
+            trash_retention_days: layers.parse("trash_retention_days", "MEMORAI_TRASH_RETENTION_DAYS", 30)?,
+            data_dir: layers
+                .path("data_dir", "MEMORAI_DATA_DIR")?
+                .unwrap_or_else(|| home.join(".memorai").join("data")),
+            api_url: layers.optional("url", "MEMORAI_URL")?,
+            api_key: layers.secret("api_key", "MEMORAI_API_KEY")?,
+            config_file,
+            settings: Vec::new(),
+        };
+
+        if let Some(setting) = layers.settings.iter_mut().find(|s| s.key == "data_dir") {
+            setting.value = config.data_dir.display().to_string();
+        }
+
+        let known: Vec<&str> = layers.settings.iter().map(|s| s.key).collect();
+        if let Some(unknown) = layers.file.keys().find(|k| !known.contains(&k.as_str())) {
+            anyhow::bail!(
+                "Unknown setting '{}' in {} (known settings: {})",
+                unknown,
+                config.config_file.as_deref().unwrap_or(Path::new("config file")).display(),
+                known.join(", ")
+            );
+        }
+
+        config.settings = layers.settings;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.host.trim().is_empty() {
+            anyhow::bail!("host cannot be empty");
+        }
+        if !EMBED_PROVIDERS.contains(&self.embed_provider.as_str()) {
+            anyhow::bail!(
+                "embed_provider must be one of {}, got '{}'",
+                EMBED_PROVIDERS.join(", "),
+                self.embed_provider
+            );
         }
+        if self.embed_provider == "local" && self.embed_model_path.is_none() {
+            anyhow::bail!("embed_model_path must be set for the local embedding provider");
+        }
+        let counts = [
+            ("embed_dim", self.embed_dim),
+            ("embed_batch_size", self.embed_batch_size),
+            ("embed_concurrency", self.embed_concurrency),
+            ("profile_max_memories", self.profile_max_memories),
+            ("profile_refresh_after", self.profile_refresh_after),
+        ];
+        for (key, value) in counts {
+            if value == 0 {
+                anyhow::bail!("{} must be greater than 0", key);
+            }
+        }
+        if self.profile_token_budget < MIN_TOKEN_BUDGET {
+            anyhow::bail!("profile_token_budget must be at least {}", MIN_TOKEN_BUDGET);
+        }
+        if !(-1.0..=1.0).contains(&self.ask_min_score) {
+            anyhow::bail!("ask_min_score must be between -1 and 1");
+        }
+        if !(0.0..=1.0).contains(&self.dedup_threshold) {
+            anyhow::bail!("dedup_threshold must be between 0 and 1");
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            anyhow::bail!("tls_cert and tls_key must be set together");
+        }
+
+        let urls = [
+            ("ollama_url", Some(&self.ollama_url)),
+            ("embed_url", Some(&self.embed_url)),
+            ("url", self.api_url.as_ref()),
+        ];
+        for (key, url) in urls {
+            if let Some(url) = url {
+                reqwest::Url::parse(url)
+                    .with_context(|| format!("{} is not a valid URL: '{}'", key, url))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dedup(&self) -> DedupSettings {
+        DedupSettings {
+            policy: self.dedup_policy,
+            threshold: self.dedup_threshold,
+        }
+    }
+
+    /// Name recorded on each memory for the model that produced its embedding
+    pub fn embedding_model(&self) -> String {
+        match self.embed_provider.as_str() {
+            "hash" => format!("hash-{}", self.embed_dim),
+            _ => self.embed_model.clone(),
+        }
+    }
+}
+
+/// Looks settings up in the command-line overrides, the environment and the
+/// config file, in that order, recording where each value came from.
+struct Layers {
+    file: toml::Table,
+    overrides: HashMap<&'static str, String>,
+    settings: Vec<Setting>,
+}
+
+impl Layers {
+    fn raw(&mut self, key: &'static str, env_var: &'static str) -> Result<Option<(String, Source)>> {
+        if let Some(value) = self.overrides.get(key) {
+            return Ok(Some((value.clone(), Source::Cli)));
+        }
+        if let Ok(value) = env::var(env_var) {
+            return Ok(Some((value, Source::Env)));
+        }
+
+        let value = match self.file.get(key) {
+            None => return Ok(None),
+            Some(toml::Value::String(s)) => s.clone(),
+            Some(toml::Value::Integer(i)) => i.to_string(),
+            Some(toml::Value::Float(f)) => f.to_string(),
+            Some(toml::Value::Boolean(b)) => b.to_string(),
+            Some(toml::Value::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::String(s) => Ok(s.clone()),
+                    _ => anyhow::bail!("{} in the config file must be a list of strings", key),
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join(","),
+            Some(_) => anyhow::bail!("Unsupported value for {} in the config file", key),
+        };
+        Ok(Some((value, Source::File)))
+    }
+
+    fn record(&mut self, key: &'static str, value: String, source: Source) {
+        self.settings.push(Setting { key, value, source });
+    }
+
+    fn optional(&mut self, key: &'static str, env_var: &'static str) -> Result<Option<String>> {
+        Ok(match self.raw(key, env_var)? {
+            Some((value, source)) => {
+                self.record(key, value.clone(), source);
+                Some(value)
+            }
+            None => {
+                self.record(key, "-".to_string(), Source::Default);
+                None
+            }
+        })
+    }
+
+    fn string(&mut self, key: &'static str, env_var: &'static str, default: &str) -> Result<String> {
+        self.parse(key, env_var, default.to_string())
+    }
+
+    fn path(&mut self, key: &'static str, env_var: &'static str) -> Result<Option<PathBuf>> {
+        Ok(self.optional(key, env_var)?.map(PathBuf::from))
+    }
+
+    fn secret(&mut self, key: &'static str, env_var: &'static str) -> Result<Option<String>> {
+        let value = self.optional(key, env_var)?;
+        if value.is_some() {
+            if let Some(setting) = self.settings.last_mut() {
+                setting.value = "********".to_string();
+            }
+        }
+        Ok(value)
+    }
+
+    fn list(&mut self, key: &'static str, env_var: &'static str) -> Result<Vec<String>> {
+        Ok(self
+            .optional(key, env_var)?
+            .map(|v| {
+                v.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn parse<T>(&mut self, key: &'static str, env_var: &'static str, default: T) -> Result<T>
+    where
+        T: FromStr + Display,
+        T::Err: Display,
+    {
+        let (value, source) = match self.raw(key, env_var)? {
+            Some((raw, source)) => {
+                let origin = match source {
+                    Source::Env => env_var.to_string(),
+                    other => other.to_string(),
+                };
+                let value = raw.trim().parse::<T>().map_err(|err| {
+                    anyhow::anyhow!("Invalid value '{}' for {} (from {}): {}", raw, key, origin, err)
+                })?;
+                (value, source)
+            }
+            None => (default, Source::Default),
+        };
+        self.record(key, value.to_string(), source);
+        Ok(value)
     }
 }
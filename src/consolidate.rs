@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::chat;
+use crate::config::Config;
+use crate::db::{self, Db, MemoryFilter};
+use crate::embeddings::{cosine_similarity, EmbeddingClient};
+use crate::jobs::Jobs;
+use crate::models::{ConsolidateRequest, Memory};
+
+/// Default similarity to a cluster's first memory needed to join it; lower
+/// than the dedup threshold, since these memories are related rather than
+/// the same
+const DEFAULT_THRESHOLD: f32 = 0.85;
+const DEFAULT_MAX_CLUSTER_SIZE: usize = 8;
+
+/// Cluster related memories in a collection and replace each cluster with a
+/// summary written by the chat model, reporting progress on the job `job_id`.
+///
+/// Each summary records the ids of its originals in `consolidated_from`, and
+/// each original records the summary in `consolidated_into`. Originals that
+/// already have a summary aren't clustered again.
+pub async fn run(
+    db: Db,
+    config: Config,
+    embeddings: Arc<EmbeddingClient>,
+    jobs: Jobs,
+    job_id: String,
+    req: ConsolidateRequest,
+    actor: Option<String>,
+) {
+    let result = consolidate(&db, &config, &embeddings, &jobs, &job_id, &req, actor.as_deref()).await;
+    if let Err(err) = &result {
+        tracing::error!("Consolidation failed: {:#}", err);
+    }
+    jobs.finish(&job_id, result);
+}
+
+async fn consolidate(
+    db: &Db,
+    config: &Config,
+    embeddings: &EmbeddingClient,
+    jobs: &Jobs,
+    job_id: &str,
+    req: &ConsolidateRequest,
+    actor: Option<&str>,
+) -> Result<()> {
+    // Vectors from another model aren't comparable
+    let filter = MemoryFilter {
+        collection: Some(req.collection.clone()),
+        embed_model: Some(embeddings.model().to_string()),
+        ..Default::default()
+    };
+
+    let mut memories: Vec<Memory> = db::get_memories_filtered(db, &filter)
+        .await?
+        .into_iter()
+        .filter(|m| m.consolidated_into.is_none())
+        .collect();
+    memories.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+    let clusters = cluster(
+        db,
+        &memories,
+        &filter,
+        req.threshold.unwrap_or(DEFAULT_THRESHOLD),
+        req.max_cluster_size.unwrap_or(DEFAULT_MAX_CLUSTER_SIZE),
+    )
+    .await?;
+    jobs.set_total(job_id, clusters.len());
+    tracing::info!(
+        "Consolidating {} clusters of related memories in '{}'",
+        clusters.len(),
+        req.collection
+    );
+
+    for members in clusters {
+        let cluster: Vec<&Memory> = members.iter().map(|&i| &memories[i]).collect();
+        match summarize(db, config, embeddings, &req.collection, cluster, req.archive, actor).await {
+            Ok(()) => jobs.record_success(job_id),
+            Err(err) => {
+                let ids: Vec<String> = members
+                    .iter()
+                    .filter_map(|&i| memories[i].id.as_ref().map(|t| t.id.to_string()))
+                    .collect();
+                jobs.record_failure(job_id, format!("{}: {:#}", ids.join(", "), err));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Group memories greedily, oldest first: each memory not yet in a cluster
+/// starts one with those of its nearest neighbours that are similar enough
+/// and also unclustered. Returns clusters of at least two, as indexes into
+/// `memories`.
+async fn cluster(
+    db: &Db,
+    memories: &[Memory],
+    filter: &MemoryFilter,
+    threshold: f32,
+    max_size: usize,
+) -> Result<Vec<Vec<usize>>> {
+    let index: HashMap<String, usize> = memories
+        .iter()
+        .enumerate()
+        .filter_map(|(i, m)| m.id.as_ref().map(|t| (t.to_string(), i)))
+        .collect();
+
+    let mut clustered = vec![false; memories.len()];
+    let mut clusters = Vec::new();
+
+    for (seed, memory) in memories.iter().enumerate() {
+        if clustered[seed] {
+            continue;
+        }
+
+        let neighbours = db::search_nearest(db, memory.embedding.clone(), max_size * 2, filter)
+            .await
+            .context("Vector index unavailable; run `memorai reembed` first")?;
+
+        let mut scored: Vec<(usize, f32)> = neighbours
+            .iter()
+            .filter_map(|n| index.get(&n.id.as_ref()?.to_string()).copied())
+            .filter(|&i| i != seed && !clustered[i])
+            .map(|i| (i, cosine_similarity(&memory.embedding, &memories[i].embedding)))
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut members = vec![seed];
+        members.extend(scored.into_iter().take(max_size - 1).map(|(i, _)| i));
+        if members.len() < 2 {
+            continue;
+        }
+
+        for &i in &members {
+            clustered[i] = true;
+        }
+        members.sort_unstable();
+        clusters.push(members);
+    }
+
+    Ok(clusters)
+}
+
+/// Ask the chat model to merge a cluster into one memory and store it.
+async fn summarize(
+    db: &Db,
+    config: &Config,
+    embeddings: &EmbeddingClient,
+    collection: &str,
+    cluster: Vec<&Memory>,
+    archive: bool,
+    actor: Option<&str>,
+) -> Result<()> {
+    let notes = cluster
+        .iter()
+        .enumerate()
+        .map(|(i, m)| format!("{}. ({}) {}", i + 1, m.created_at.get(..10).unwrap_or(&m.created_at), m.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        "The following notes overlap. Merge them into a single concise note that keeps every distinct fact \
+         and drops repetition. Where notes conflict, prefer the most recent one. \
+         Reply with the merged note only, without a preamble.\n\n\
+         Notes (oldest first):\n{}\n\n\
+         Merged note:",
+        notes
+    );
+
+    let summary = chat::generate(config, prompt).await?;
+    if summary.is_empty() {
+        anyhow::bail!("chat model returned an empty summary");
+    }
+
+    let embedding = embeddings.embed(&summary).await?;
+    if embedding.len() != config.embed_dim {
+        anyhow::bail!(
+            "model returned {} dimensions but MEMORAI_EMBED_DIM is {}",
+            embedding.len(),
+            config.embed_dim
+        );
+    }
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in cluster.iter().flat_map(|m| &m.tags) {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+
+    let summary = db::Summary {
+        text: summary,
+        tags,
+        embedding,
+        embed_model: embeddings.model().to_string(),
+        originals: cluster.iter().filter_map(|m| m.id.clone()).collect(),
+    };
+    db::store_consolidated(db, collection, summary, archive, actor).await
+}
@@ -1,12 +1,128 @@
+use std::collections::HashSet;
+
 use anyhow::{Context, Result};
 use surrealdb::engine::local::RocksDb;
+use surrealdb::method::Query;
+use surrealdb::sql::{Datetime, Thing};
 use surrealdb::Surreal;
 
 use crate::config::Config;
-use crate::models::Memory;
+use crate::dedup::content_hash;
+use crate::models::{
+    Collection, CreateMemoryRequest, Memory, MemoryTags, MemoryText, MemoryVector, ProfileSnapshot, Revision,
+};
+use crate::profile::Strategy;
 
 pub type Db = Surreal<surrealdb::engine::local::Db>;
 
+/// Collection used by the unscoped `/v1` routes and when no `--collection`
+/// is given
+pub const DEFAULT_COLLECTION: &str = "default";
+
+/// Upper bound on query terms scored individually by keyword search
+const MAX_KEYWORD_TERMS: usize = 16;
+
+/// Whether a memory is in use, archived (e.g. after consolidation) or in the
+/// trash waiting to be purged.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MemoryState {
+    #[default]
+    Active,
+    Archived,
+    Trashed,
+}
+
+impl MemoryState {
+    fn condition(self) -> &'static str {
+        match self {
+            Self::Active => "deleted_at IS NONE AND archived_at IS NONE",
+            Self::Archived => "deleted_at IS NONE AND archived_at IS NOT NONE",
+            Self::Trashed => "deleted_at IS NOT NONE",
+        }
+    }
+}
+
+/// Metadata conditions on memories, evaluated inside the database query.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFilter {
+    pub collection: Option<String>,
+    pub tags: Vec<String>,
+    /// Require every tag instead of any of them
+    pub match_all_tags: bool,
+    pub source: Option<String>,
+    pub created_after: Option<Datetime>,
+    pub created_before: Option<Datetime>,
+    pub updated_after: Option<Datetime>,
+    pub updated_before: Option<Datetime>,
+    /// Only memories embedded with this model
+    pub embed_model: Option<String>,
+    /// Archived or trashed memories instead of active ones
+    pub state: MemoryState,
+}
+
+impl MemoryFilter {
+    fn conditions(&self) -> Vec<&'static str> {
+        let mut conditions = Vec::new();
+        if self.collection.is_some() {
+            conditions.push("collection = $filter_collection");
+        }
+        if !self.tags.is_empty() {
+            conditions.push(if self.match_all_tags {
+                "tags CONTAINSALL $filter_tags"
+            } else {
+                "tags CONTAINSANY $filter_tags"
+            });
+        }
+        if self.source.is_some() {
+            conditions.push("source = $filter_source");
+        }
+        if self.created_after.is_some() {
+            conditions.push("created_at >= $created_after");
+        }
+        if self.created_before.is_some() {
+            conditions.push("created_at <= $created_before");
+        }
+        if self.updated_after.is_some() {
+            conditions.push("updated_at >= $updated_after");
+        }
+        if self.updated_before.is_some() {
+            conditions.push("updated_at <= $updated_before");
+        }
+        if self.embed_model.is_some() {
+            conditions.push("embed_model = $filter_embed_model");
+        }
+        conditions.push(self.state.condition());
+        conditions
+    }
+
+    /// ` WHERE ...` combining `base` (if any) with the filter conditions, or
+    /// an empty string when there is nothing to filter on.
+    fn where_clause(&self, base: Option<&str>) -> String {
+        let mut conditions: Vec<String> = base.map(|b| vec![b.to_string()]).unwrap_or_default();
+        conditions.extend(self.conditions().into_iter().map(String::from));
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        }
+    }
+
+    fn bind<'r>(
+        &self,
+        query: Query<'r, surrealdb::engine::local::Db>,
+    ) -> Query<'r, surrealdb::engine::local::Db> {
+        query
+            .bind(("filter_collection", self.collection.clone()))
+            .bind(("filter_tags", self.tags.clone()))
+            .bind(("filter_source", self.source.clone()))
+            .bind(("created_after", self.created_after.clone()))
+            .bind(("created_before", self.created_before.clone()))
+            .bind(("updated_after", self.updated_after.clone()))
+            .bind(("updated_before", self.updated_before.clone()))
+            .bind(("filter_embed_model", self.embed_model.clone()))
+    }
+}
+
 pub async fn init_db(config: &Config) -> Result<Db> {
     // Ensure data directory exists
     std::fs::create_dir_all(&config.data_dir)
@@ -31,31 +147,193 @@ pub async fn init_db(config: &Config) -> Result<Db> {
          DEFINE FIELD IF NOT EXISTS source ON TABLE memory TYPE option<string>;
          DEFINE FIELD IF NOT EXISTS embedding ON TABLE memory TYPE array;
          DEFINE FIELD IF NOT EXISTS embedding.* ON TABLE memory TYPE float;
+         DEFINE FIELD IF NOT EXISTS staged_embedding ON TABLE memory TYPE option<array<float>>;
+         DEFINE FIELD IF NOT EXISTS staged_model ON TABLE memory TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS collection ON TABLE memory TYPE string DEFAULT 'default';
+         DEFINE FIELD IF NOT EXISTS embed_model ON TABLE memory TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS embed_dim ON TABLE memory TYPE option<int>;
+         DEFINE FIELD IF NOT EXISTS source_hash ON TABLE memory TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS content_hash ON TABLE memory TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS archived_at ON TABLE memory TYPE option<datetime>;
+         DEFINE FIELD IF NOT EXISTS consolidated_from ON TABLE memory TYPE option<array<string>>;
+         DEFINE FIELD IF NOT EXISTS consolidated_into ON TABLE memory TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS deleted_at ON TABLE memory TYPE option<datetime>;
+         DEFINE FIELD IF NOT EXISTS updated_by ON TABLE memory TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS auto_tags ON TABLE memory TYPE option<array<string>>;
          DEFINE FIELD IF NOT EXISTS created_at ON TABLE memory TYPE datetime;
          DEFINE FIELD IF NOT EXISTS updated_at ON TABLE memory TYPE datetime;
          DEFINE INDEX IF NOT EXISTS idx_tags ON TABLE memory FIELDS tags;
-         DEFINE INDEX IF NOT EXISTS idx_source ON TABLE memory FIELDS source;",
+         DEFINE INDEX IF NOT EXISTS idx_source ON TABLE memory FIELDS source;
+         DEFINE ANALYZER IF NOT EXISTS memory_text TOKENIZERS blank, punct FILTERS lowercase, ascii;
+         DEFINE INDEX IF NOT EXISTS idx_text ON TABLE memory FIELDS text SEARCH ANALYZER memory_text BM25;
+         DEFINE INDEX IF NOT EXISTS idx_embed_model ON TABLE memory FIELDS embed_model;
+         DEFINE INDEX IF NOT EXISTS idx_collection ON TABLE memory FIELDS collection;
+         DEFINE INDEX IF NOT EXISTS idx_content_hash ON TABLE memory FIELDS content_hash;
+         DEFINE TABLE IF NOT EXISTS collection SCHEMAFULL;
+         DEFINE FIELD IF NOT EXISTS name ON TABLE collection TYPE string;
+         DEFINE FIELD IF NOT EXISTS description ON TABLE collection TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS created_at ON TABLE collection TYPE datetime;
+         DEFINE TABLE IF NOT EXISTS memory_revision SCHEMAFULL;
+         DEFINE FIELD IF NOT EXISTS memory ON TABLE memory_revision TYPE string;
+         DEFINE FIELD IF NOT EXISTS collection ON TABLE memory_revision TYPE string;
+         DEFINE FIELD IF NOT EXISTS action ON TABLE memory_revision TYPE string;
+         DEFINE FIELD IF NOT EXISTS text ON TABLE memory_revision TYPE string;
+         DEFINE FIELD IF NOT EXISTS tags ON TABLE memory_revision TYPE array<string>;
+         DEFINE FIELD IF NOT EXISTS source ON TABLE memory_revision TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS actor ON TABLE memory_revision TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS created_at ON TABLE memory_revision TYPE datetime;
+         DEFINE INDEX IF NOT EXISTS idx_revision_memory ON TABLE memory_revision FIELDS memory;
+         DEFINE TABLE IF NOT EXISTS profile_snapshot SCHEMAFULL;
+         DEFINE FIELD IF NOT EXISTS collection ON TABLE profile_snapshot TYPE string;
+         DEFINE FIELD IF NOT EXISTS profile ON TABLE profile_snapshot TYPE string;
+         DEFINE FIELD IF NOT EXISTS model ON TABLE profile_snapshot TYPE string;
+         DEFINE FIELD IF NOT EXISTS strategy ON TABLE profile_snapshot TYPE string;
+         DEFINE FIELD IF NOT EXISTS tags ON TABLE profile_snapshot TYPE array<string>;
+         DEFINE FIELD IF NOT EXISTS memories ON TABLE profile_snapshot TYPE array<string>;
+         DEFINE FIELD IF NOT EXISTS chunks ON TABLE profile_snapshot TYPE int;
+         DEFINE FIELD IF NOT EXISTS incremental ON TABLE profile_snapshot TYPE bool;
+         DEFINE FIELD IF NOT EXISTS previous ON TABLE profile_snapshot TYPE option<string>;
+         DEFINE FIELD IF NOT EXISTS created_at ON TABLE profile_snapshot TYPE datetime;
+         DEFINE INDEX IF NOT EXISTS idx_profile_collection ON TABLE profile_snapshot FIELDS collection;",
     )
     .await
     .context("Failed to define schema")?;
 
+    // Every change to a memory's content or state appends a revision, in the
+    // same transaction as the change, whichever code path made it. Revisions
+    // go when their memory is permanently deleted.
+    db.query(
+        "DEFINE EVENT OVERWRITE memory_history ON TABLE memory
+         WHEN $event != 'UPDATE'
+             OR $before.text != $after.text
+             OR $before.tags != $after.tags
+             OR $before.source != $after.source
+             OR $before.archived_at != $after.archived_at
+             OR $before.deleted_at != $after.deleted_at
+         THEN {
+             IF $event = 'DELETE' {
+                 DELETE memory_revision WHERE memory = record::id($before.id);
+             } ELSE {
+                 CREATE memory_revision SET
+                     memory = record::id($after.id),
+                     collection = $after.collection,
+                     action = IF $event = 'CREATE' THEN 'create'
+                         ELSE IF $before.deleted_at IS NONE AND $after.deleted_at IS NOT NONE THEN 'delete'
+                         ELSE IF $before.deleted_at IS NOT NONE AND $after.deleted_at IS NONE THEN 'restore'
+                         ELSE IF $before.archived_at IS NONE AND $after.archived_at IS NOT NONE THEN 'archive'
+                         ELSE 'update' END,
+                     text = $after.text,
+                     tags = $after.tags,
+                     source = $after.source,
+                     actor = $after.updated_by,
+                     created_at = time::now();
+             };
+         };",
+    )
+    .await
+    .context("Failed to define memory history")?
+    .check()
+    .context("Failed to define memory history")?;
+
+    // The default collection always exists and holds memories stored before
+    // collections were introduced
+    db.query(
+        "INSERT IGNORE INTO collection { id: $name, name: $name, created_at: time::now() };
+         UPDATE memory SET collection = $name WHERE collection IS NONE;",
+    )
+    .bind(("name", DEFAULT_COLLECTION))
+    .await
+    .context("Failed to set up default collection")?
+    .check()
+    .context("Failed to set up default collection")?;
+
+    // Memories stored before models were tracked: assume the configured model
+    // produced them if the dimensions line up
+    db.query("UPDATE memory SET embed_model = $model, embed_dim = array::len(embedding) WHERE embed_model IS NONE AND array::len(embedding) = $dim")
+        .bind(("model", config.embedding_model()))
+        .bind(("dim", config.embed_dim))
+        .await
+        .context("Failed to backfill embedding models")?;
+
+    backfill_content_hashes(&db).await?;
+    backfill_revisions(&db).await?;
+
+    match vector_index_dimension(&db).await? {
+        None => define_vector_index(&db, config.embed_dim).await?,
+        Some(dim) if dim != config.embed_dim => tracing::warn!(
+            "Vector index has dimension {} but MEMORAI_EMBED_DIM is {}; run `memorai reembed` to rebuild it",
+            dim,
+            config.embed_dim
+        ),
+        Some(_) => {}
+    }
+
     tracing::info!("Database initialized at {}", path);
     Ok(db)
 }
 
+/// Dimension of the HNSW index on `embedding`, or `None` if it isn't defined.
+pub async fn vector_index_dimension(db: &Db) -> Result<Option<usize>> {
+    let mut result = db
+        .query("INFO FOR TABLE memory")
+        .await
+        .context("Failed to read table info")?;
+
+    let info: Option<serde_json::Value> = result.take(0).context("Failed to parse table info")?;
+    let definition = info
+        .as_ref()
+        .and_then(|i| i["indexes"]["idx_embedding"].as_str())
+        .map(String::from);
+
+    Ok(definition.and_then(|d| {
+        d.split_whitespace()
+            .skip_while(|w| *w != "DIMENSION")
+            .nth(1)
+            .and_then(|n| n.parse().ok())
+    }))
+}
+
+pub async fn define_vector_index(db: &Db, dim: usize) -> Result<()> {
+    // DIMENSION has to be a literal
+    db.query(format!(
+        "DEFINE INDEX IF NOT EXISTS idx_embedding ON TABLE memory FIELDS embedding HNSW DIMENSION {} DIST COSINE TYPE F32",
+        dim
+    ))
+    .await
+    .context("Failed to define vector index")?
+    .check()
+    .context("Failed to define vector index")?;
+    Ok(())
+}
+
+pub async fn remove_vector_index(db: &Db) -> Result<()> {
+    db.query("REMOVE INDEX IF EXISTS idx_embedding ON TABLE memory")
+        .await
+        .context("Failed to remove vector index")?
+        .check()
+        .context("Failed to remove vector index")?;
+    Ok(())
+}
+
 pub async fn create_memory(
     db: &Db,
-    text: String,
-    tags: Vec<String>,
-    source: Option<String>,
+    collection: &str,
+    req: CreateMemoryRequest,
     embedding: Vec<f32>,
+    embed_model: &str,
+    actor: Option<&str>,
 ) -> Result<Memory> {
     let mut result = db
-        .query("CREATE memory SET text = $text, tags = $tags, source = $source, embedding = $embedding, created_at = time::now(), updated_at = time::now()")
-        .bind(("text", text))
-        .bind(("tags", tags))
-        .bind(("source", source))
+        .query("CREATE memory SET collection = $collection, text = $text, content_hash = $content_hash, tags = $tags, source = $source, embedding = $embedding, embed_model = $embed_model, embed_dim = array::len($embedding), auto_tags = $auto_tags, created_at = time::now(), updated_at = time::now(), updated_by = $actor")
+        .bind(("collection", collection.to_string()))
+        .bind(("content_hash", content_hash(&req.text)))
+        .bind(("text", req.text))
+        .bind(("tags", req.tags))
+        .bind(("auto_tags", (!req.auto_tags.is_empty()).then_some(req.auto_tags)))
+        .bind(("source", req.source))
         .bind(("embedding", embedding))
+        .bind(("embed_model", embed_model.to_string()))
+        .bind(("actor", actor.map(String::from)))
         .await
         .context("Failed to create memory")?;
 
@@ -63,12 +341,716 @@ pub async fn create_memory(
     memory.context("No memory returned after creation")
 }
 
-pub async fn get_all_memories(db: &Db) -> Result<Vec<Memory>> {
-    let memories: Vec<Memory> = db
-        .select("memory")
+pub async fn get_memory(db: &Db, collection: &str, id: &str) -> Result<Option<Memory>> {
+    let memory: Option<Memory> = db
+        .select(("memory", id))
+        .await
+        .context("Failed to fetch memory")?;
+
+    Ok(memory.filter(|m| m.collection == collection))
+}
+
+/// Fields to change on a memory; `None` leaves a field as it is.
+#[derive(Debug, Default)]
+pub struct MemoryUpdate {
+    pub text: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// `Some(None)` clears the source
+    pub source: Option<Option<String>>,
+    /// New embedding and the model that produced it
+    pub embedding: Option<(Vec<f32>, String)>,
+}
+
+pub async fn update_memory(
+    db: &Db,
+    collection: &str,
+    id: &str,
+    update: MemoryUpdate,
+    actor: Option<&str>,
+) -> Result<Option<Memory>> {
+    let mut sets = vec!["updated_at = time::now()", "updated_by = $actor"];
+    if update.text.is_some() {
+        sets.push("text = $text");
+        sets.push("content_hash = $content_hash");
+    }
+    if update.tags.is_some() {
+        // Suggested tags that are kept stay marked as suggested
+        sets.push("auto_tags = array::intersect(auto_tags ?? [], $tags)");
+        sets.push("tags = $tags");
+    }
+    if update.source.is_some() {
+        sets.push("source = $source");
+    }
+    if update.embedding.is_some() {
+        sets.push("embedding = $embedding");
+        sets.push("embed_model = $embed_model");
+        sets.push("embed_dim = array::len($embedding)");
+    }
+
+    let sql = format!(
+        "UPDATE type::thing('memory', $id) SET {} WHERE collection = $collection AND deleted_at IS NONE RETURN AFTER",
+        sets.join(", ")
+    );
+
+    let (embedding, embed_model) = update.embedding.unzip();
+    let mut result = db
+        .query(sql)
+        .bind(("id", id.to_string()))
+        .bind(("collection", collection.to_string()))
+        .bind(("content_hash", update.text.as_deref().map(content_hash)))
+        .bind(("text", update.text))
+        .bind(("tags", update.tags))
+        .bind(("source", update.source.flatten()))
+        .bind(("embedding", embedding))
+        .bind(("embed_model", embed_model))
+        .bind(("actor", actor.map(String::from)))
+        .await
+        .context("Failed to update memory")?;
+
+    let updated: Vec<Memory> = result.take(0).context("Failed to parse updated memory")?;
+    Ok(updated.into_iter().next())
+}
+
+/// A memory in the collection with the given `content_hash`, if any.
+pub async fn find_by_content_hash(db: &Db, collection: &str, hash: &str) -> Result<Option<Memory>> {
+    let mut result = db
+        .query("SELECT * FROM memory WHERE collection = $collection AND content_hash = $hash AND archived_at IS NONE AND deleted_at IS NONE LIMIT 1")
+        .bind(("collection", collection.to_string()))
+        .bind(("hash", hash.to_string()))
+        .await
+        .context("Failed to query duplicates")?;
+
+    let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories.into_iter().next())
+}
+
+/// Fold a duplicate into an existing memory: add its tags, marking the
+/// suggested ones the memory didn't already have as suggested, keep the
+/// existing source unless there is none, and bump `updated_at`.
+pub async fn merge_memory(
+    db: &Db,
+    id: &str,
+    tags: Vec<String>,
+    auto_tags: Vec<String>,
+    source: Option<String>,
+    actor: Option<&str>,
+) -> Result<Memory> {
+    let mut result = db
+        .query("UPDATE type::thing('memory', $id) SET auto_tags = array::union(auto_tags ?? [], array::complement($auto_tags, tags)), tags = array::union(tags, $tags), source = source ?? $source, updated_at = time::now(), updated_by = $actor RETURN AFTER")
+        .bind(("id", id.to_string()))
+        .bind(("tags", tags))
+        .bind(("auto_tags", auto_tags))
+        .bind(("source", source))
+        .bind(("actor", actor.map(String::from)))
+        .await
+        .context("Failed to merge memory")?;
+
+    let merged: Vec<Memory> = result.take(0).context("Failed to parse merged memory")?;
+    merged.into_iter().next().context("Memory to merge into no longer exists")
+}
+
+/// Hashes recorded on the memories ingested from `source`, one per memory
+/// not in the trash.
+pub async fn document_hashes(db: &Db, collection: &str, source: &str) -> Result<Vec<Option<String>>> {
+    let mut result = db
+        .query("SELECT VALUE source_hash FROM memory WHERE collection = $collection AND source = $source AND deleted_at IS NONE")
+        .bind(("collection", collection.to_string()))
+        .bind(("source", source.to_string()))
+        .await
+        .context("Failed to query document")?;
+
+    let hashes: Vec<Option<String>> = result.take(0).context("Failed to parse document hashes")?;
+    Ok(hashes)
+}
+
+/// A document split into chunks and embedded, ready to store.
+#[derive(Debug)]
+pub struct DocumentChunks {
+    /// Hash of the document and ingest options, stored on every chunk
+    pub hash: String,
+    pub tags: Vec<String>,
+    /// Each chunk's text and embedding
+    pub chunks: Vec<(String, Vec<f32>)>,
+    pub embed_model: String,
+}
+
+/// Replace every memory from `source`, including any in the trash, with the
+/// given chunks, in one transaction so a failed ingest leaves the old ones.
+pub async fn replace_document(
+    db: &Db,
+    collection: &str,
+    source: &str,
+    document: DocumentChunks,
+    actor: Option<&str>,
+) -> Result<()> {
+    let chunks: Vec<serde_json::Value> = document
+        .chunks
+        .into_iter()
+        .map(|(text, embedding)| {
+            serde_json::json!({ "text": text, "content_hash": content_hash(&text), "embedding": embedding })
+        })
+        .collect();
+
+    db.query(
+        "BEGIN TRANSACTION;
+         DELETE memory WHERE collection = $collection AND source = $source;
+         FOR $chunk IN $chunks {
+             CREATE memory SET collection = $collection, text = $chunk.text, content_hash = $chunk.content_hash, tags = $tags, source = $source, source_hash = $hash, embedding = $chunk.embedding, embed_model = $embed_model, embed_dim = array::len($chunk.embedding), created_at = time::now(), updated_at = time::now(), updated_by = $actor;
+         };
+         COMMIT TRANSACTION;",
+    )
+    .bind(("collection", collection.to_string()))
+    .bind(("source", source.to_string()))
+    .bind(("hash", document.hash))
+    .bind(("tags", document.tags))
+    .bind(("chunks", chunks))
+    .bind(("embed_model", document.embed_model))
+    .bind(("actor", actor.map(String::from)))
+    .await
+    .context("Failed to store document")?
+    .check()
+    .context("Failed to store document")?;
+    Ok(())
+}
+
+/// A memory written by the chat model to replace several others.
+#[derive(Debug)]
+pub struct Summary {
+    pub text: String,
+    pub tags: Vec<String>,
+    pub embedding: Vec<f32>,
+    pub embed_model: String,
+    /// Memories the summary was written from
+    pub originals: Vec<Thing>,
+}
+
+/// Store a summary and link its originals to it, archiving them if `archive`
+/// is set.
+pub async fn store_consolidated(
+    db: &Db,
+    collection: &str,
+    summary: Summary,
+    archive: bool,
+    actor: Option<&str>,
+) -> Result<()> {
+    let from: Vec<String> = summary.originals.iter().map(|t| t.id.to_string()).collect();
+
+    db.query(
+        "BEGIN TRANSACTION;
+         LET $summary = (CREATE memory SET collection = $collection, text = $text, content_hash = $content_hash, tags = $tags, source = 'consolidation', consolidated_from = $from, embedding = $embedding, embed_model = $embed_model, embed_dim = array::len($embedding), created_at = time::now(), updated_at = time::now(), updated_by = $actor)[0];
+         UPDATE $originals SET consolidated_into = record::id($summary.id), archived_at = IF $archive THEN time::now() ELSE NONE END, updated_by = $actor;
+         COMMIT TRANSACTION;",
+    )
+    .bind(("collection", collection.to_string()))
+    .bind(("content_hash", content_hash(&summary.text)))
+    .bind(("text", summary.text))
+    .bind(("tags", summary.tags))
+    .bind(("from", from))
+    .bind(("embedding", summary.embedding))
+    .bind(("embed_model", summary.embed_model))
+    .bind(("originals", summary.originals))
+    .bind(("archive", archive))
+    .bind(("actor", actor.map(String::from)))
+    .await
+    .context("Failed to store consolidated memory")?
+    .check()
+    .context("Failed to store consolidated memory")?;
+    Ok(())
+}
+
+/// A memory read from an export, keeping its id and timestamps when present.
+#[derive(Debug)]
+pub struct ImportedMemory {
+    pub id: Option<String>,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub created_at: Option<Datetime>,
+    pub updated_at: Option<Datetime>,
+    pub archived_at: Option<Datetime>,
+    pub embedding: Vec<f32>,
+    pub embed_model: String,
+}
+
+/// Store an imported memory. Returns `false` without writing anything when a
+/// memory with the same id exists in the collection, unless `overwrite` is
+/// set, which also takes it out of the trash. Ids taken by another
+/// collection's memories are never replaced; the memory gets a new id
+/// instead.
+pub async fn import_memory(
+    db: &Db,
+    collection: &str,
+    memory: ImportedMemory,
+    overwrite: bool,
+    actor: Option<&str>,
+) -> Result<bool> {
+    let statement = match &memory.id {
+        Some(id) => {
+            let existing: Option<Memory> = db
+                .select(("memory", id.as_str()))
+                .await
+                .context("Failed to check for existing memory")?;
+            match existing {
+                Some(m) if overwrite && m.collection == collection => {
+                    "UPDATE type::thing('memory', $id)"
+                }
+                Some(m) if m.collection == collection => return Ok(false),
+                Some(_) => "CREATE memory",
+                None => "CREATE type::thing('memory', $id)",
+            }
+        }
+        None => "CREATE memory",
+    };
+
+    let sql = format!(
+        "{} SET collection = $collection, text = $text, content_hash = $content_hash, tags = $tags, source = $source, embedding = $embedding, embed_model = $embed_model, embed_dim = array::len($embedding), created_at = $created_at ?? time::now(), updated_at = $updated_at ?? time::now(), archived_at = $archived_at, deleted_at = NONE, updated_by = $actor",
+        statement
+    );
+
+    db.query(sql)
+        .bind(("id", memory.id))
+        .bind(("collection", collection.to_string()))
+        .bind(("content_hash", content_hash(&memory.text)))
+        .bind(("text", memory.text))
+        .bind(("tags", memory.tags))
+        .bind(("source", memory.source))
+        .bind(("embedding", memory.embedding))
+        .bind(("embed_model", memory.embed_model))
+        .bind(("created_at", memory.created_at))
+        .bind(("updated_at", memory.updated_at))
+        .bind(("archived_at", memory.archived_at))
+        .bind(("actor", actor.map(String::from)))
+        .await
+        .context("Failed to import memory")?
+        .check()
+        .context("Failed to import memory")?;
+    Ok(true)
+}
+
+/// Those of `ids` taken by memories in the collection, trashed or not.
+pub async fn existing_memory_ids(db: &Db, collection: &str, ids: &[String]) -> Result<HashSet<String>> {
+    let things: Vec<Thing> = ids.iter().map(|id| Thing::from(("memory", id.as_str()))).collect();
+    let mut result = db
+        .query("SELECT VALUE record::id(id) FROM $ids WHERE collection = $collection")
+        .bind(("ids", things))
+        .bind(("collection", collection.to_string()))
+        .await
+        .context("Failed to look up memories")?;
+
+    let existing: Vec<String> = result.take(0).context("Failed to parse memory ids")?;
+    Ok(existing.into_iter().collect())
+}
+
+/// Ids and texts of memories whose embedding wasn't produced by `embed_model`
+/// (or of every memory when `all` is set).
+pub async fn get_stale_memories(db: &Db, embed_model: &str, all: bool) -> Result<Vec<MemoryText>> {
+    let sql = if all {
+        "SELECT id, text FROM memory"
+    } else {
+        "SELECT id, text FROM memory WHERE embed_model != $embed_model"
+    };
+
+    let mut result = db
+        .query(sql)
+        .bind(("embed_model", embed_model.to_string()))
+        .await
+        .context("Failed to query stale memories")?;
+
+    let memories: Vec<MemoryText> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// Active and archived memories whose embedding wasn't produced by
+/// `embed_model`, in one collection or across all of them. Trashed memories
+/// aren't counted, as with the totals from `count_memories`.
+pub async fn count_stale_embeddings(
+    db: &Db,
+    embed_model: &str,
+    collection: Option<&str>,
+) -> Result<usize> {
+    let sql = if collection.is_some() {
+        "SELECT count() FROM memory WHERE embed_model != $embed_model AND collection = $collection AND deleted_at IS NONE GROUP ALL"
+    } else {
+        "SELECT count() FROM memory WHERE embed_model != $embed_model AND deleted_at IS NONE GROUP ALL"
+    };
+
+    let mut result = db
+        .query(sql)
+        .bind(("embed_model", embed_model.to_string()))
+        .bind(("collection", collection.map(String::from)))
+        .await
+        .context("Failed to count stale embeddings")?;
+
+    #[derive(serde::Deserialize)]
+    struct CountResult {
+        count: usize,
+    }
+
+    let count: Option<CountResult> = result.take(0).ok().and_then(|v: Vec<CountResult>| v.into_iter().next());
+    Ok(count.map(|c| c.count).unwrap_or(0))
+}
+
+/// Replace a memory's embedding without touching `updated_at`; the content
+/// itself hasn't changed.
+pub async fn set_embedding(db: &Db, id: Thing, embedding: Vec<f32>, embed_model: &str) -> Result<()> {
+    db.query("UPDATE $id SET embedding = $embedding, embed_model = $embed_model, embed_dim = array::len($embedding)")
+        .bind(("id", id))
+        .bind(("embedding", embedding))
+        .bind(("embed_model", embed_model.to_string()))
+        .await
+        .context("Failed to store embedding")?
+        .check()
+        .context("Failed to store embedding")?;
+    Ok(())
+}
+
+/// Keep a new embedding beside a memory's current one until
+/// `apply_staged_embeddings`, for vectors the index can't take yet.
+pub async fn stage_embedding(db: &Db, id: Thing, embedding: Vec<f32>, embed_model: &str) -> Result<()> {
+    db.query("UPDATE $id SET staged_embedding = $embedding, staged_model = $embed_model")
+        .bind(("id", id))
+        .bind(("embedding", embedding))
+        .bind(("embed_model", embed_model.to_string()))
+        .await
+        .context("Failed to stage embedding")?
+        .check()
+        .context("Failed to stage embedding")?;
+    Ok(())
+}
+
+/// Swap every staged embedding in, in one statement.
+pub async fn apply_staged_embeddings(db: &Db) -> Result<()> {
+    db.query("UPDATE memory SET embedding = staged_embedding, embed_model = staged_model, embed_dim = array::len(staged_embedding), staged_embedding = NONE, staged_model = NONE WHERE staged_embedding IS NOT NONE")
+        .await
+        .context("Failed to apply staged embeddings")?
+        .check()
+        .context("Failed to apply staged embeddings")?;
+    Ok(())
+}
+
+pub async fn clear_staged_embeddings(db: &Db) -> Result<()> {
+    db.query("UPDATE memory SET staged_embedding = NONE, staged_model = NONE WHERE staged_embedding IS NOT NONE")
+        .await
+        .context("Failed to clear staged embeddings")?
+        .check()
+        .context("Failed to clear staged embeddings")?;
+    Ok(())
+}
+
+/// Every active (not archived or trashed) memory in a collection.
+pub async fn get_all_memories(db: &Db, collection: &str) -> Result<Vec<Memory>> {
+    let mut result = db
+        .query("SELECT * FROM memory WHERE collection = $collection AND archived_at IS NONE AND deleted_at IS NONE")
+        .bind(("collection", collection.to_string()))
+        .await
+        .context("Failed to fetch memories")?;
+
+    let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// Approximate nearest neighbours via the HNSW index on `embedding`.
+pub async fn search_nearest(
+    db: &Db,
+    embedding: Vec<f32>,
+    k: usize,
+    filter: &MemoryFilter,
+) -> Result<Vec<Memory>> {
+    // K and EF have to be literals in SurrealQL's KNN operator
+    let ef = (k * 4).max(40);
+    let knn = format!("embedding <|{},{}|> $embedding", k, ef);
+    // Pin the HNSW index; otherwise the planner may pick idx_tags/idx_source
+    // for the filter conditions and skip the KNN operator entirely
+    let sql = format!(
+        "SELECT * FROM memory WITH INDEX idx_embedding{}",
+        filter.where_clause(Some(&knn))
+    );
+
+    let mut result = filter
+        .bind(db.query(sql).bind(("embedding", embedding)))
+        .await
+        .context("Failed to query nearest memories")?;
+
+    let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// Full-text matches on `text`, paired with their BM25 score.
+///
+/// SurrealQL's `@@` requires every term to match, so each query term gets its
+/// own match reference and the scores are summed; a memory matching any term
+/// is returned.
+pub async fn search_keyword(
+    db: &Db,
+    query: &str,
+    limit: usize,
+    filter: &MemoryFilter,
+) -> Result<Vec<(Memory, f32)>> {
+    #[derive(serde::Deserialize)]
+    struct KeywordHit {
+        memory: Memory,
+        keyword_score: f32,
+    }
+
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .take(MAX_KEYWORD_TERMS)
+        .map(|t| t.to_string())
+        .collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let matches: Vec<String> = (1..=terms.len())
+        .map(|i| format!("text @{}@ $term{}", i, i))
+        .collect();
+    let scores: Vec<String> = (1..=terms.len())
+        .map(|i| format!("(search::score({}) ?? 0)", i))
+        .collect();
+    let matches = format!("({})", matches.join(" OR "));
+    let sql = format!(
+        "SELECT id AS memory, {} AS keyword_score FROM memory{} ORDER BY keyword_score DESC LIMIT $limit FETCH memory",
+        scores.join(" + "),
+        filter.where_clause(Some(&matches))
+    );
+
+    let mut q = filter.bind(db.query(sql).bind(("limit", limit)));
+    for (i, term) in terms.into_iter().enumerate() {
+        q = q.bind((format!("term{}", i + 1), term));
+    }
+
+    let mut result = q.await.context("Failed to run keyword search")?;
+    let hits: Vec<KeywordHit> = result.take(0).context("Failed to parse keyword matches")?;
+    Ok(hits.into_iter().map(|h| (h.memory, h.keyword_score)).collect())
+}
+
+pub async fn get_memories_filtered(db: &Db, filter: &MemoryFilter) -> Result<Vec<Memory>> {
+    let sql = format!("SELECT * FROM memory{}", filter.where_clause(None));
+
+    let mut result = filter
+        .bind(db.query(sql))
+        .await
+        .context("Failed to query memories")?;
+
+    let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// Up to `limit` memories matching `filter`, picked at random, with only
+/// their id, creation time and embedding.
+pub async fn sample_vectors(db: &Db, filter: &MemoryFilter, limit: usize) -> Result<Vec<MemoryVector>> {
+    let sql = format!(
+        "SELECT id, embedding, created_at FROM memory{} ORDER BY rand() LIMIT $limit",
+        filter.where_clause(None)
+    );
+
+    let mut result = filter
+        .bind(db.query(sql).bind(("limit", limit)))
+        .await
+        .context("Failed to sample memories")?;
+
+    let memories: Vec<MemoryVector> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// The id and tags of every memory matching `filter`, newest first.
+pub async fn get_memory_tags(db: &Db, filter: &MemoryFilter) -> Result<Vec<MemoryTags>> {
+    // Ordering needs `created_at` in the selection
+    let sql = format!(
+        "SELECT id, tags, created_at FROM memory{} ORDER BY created_at DESC",
+        filter.where_clause(None)
+    );
+
+    let mut result = filter.bind(db.query(sql)).await.context("Failed to query memories")?;
+
+    let memories: Vec<MemoryTags> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// The memories with these ids, in no particular order.
+pub async fn get_memories_by_ids(db: &Db, ids: Vec<Thing>) -> Result<Vec<Memory>> {
+    let mut result = db
+        .query("SELECT * FROM $ids")
+        .bind(("ids", ids))
         .await
         .context("Failed to fetch memories")?;
 
+    let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
+    Ok(memories)
+}
+
+/// Hash the text of memories stored before duplicates were detected.
+async fn backfill_content_hashes(db: &Db) -> Result<()> {
+    let mut result = db
+        .query("SELECT id, text FROM memory WHERE content_hash IS NONE")
+        .await
+        .context("Failed to query memories without content hashes")?;
+    let memories: Vec<MemoryText> = result.take(0).context("Failed to parse memories")?;
+
+    for memory in &memories {
+        db.query("UPDATE $id SET content_hash = $hash")
+            .bind(("id", memory.id.clone()))
+            .bind(("hash", content_hash(&memory.text)))
+            .await
+            .context("Failed to backfill content hash")?
+            .check()
+            .context("Failed to backfill content hash")?;
+    }
+    if !memories.is_empty() {
+        tracing::info!("Hashed the text of {} existing memories", memories.len());
+    }
+    Ok(())
+}
+
+/// Revisions of a memory, newest first.
+pub async fn get_revisions(db: &Db, memory_id: &str) -> Result<Vec<Revision>> {
+    let mut result = db
+        .query("SELECT * FROM memory_revision WHERE memory = $memory ORDER BY created_at DESC")
+        .bind(("memory", memory_id.to_string()))
+        .await
+        .context("Failed to query revisions")?;
+
+    let revisions: Vec<Revision> = result.take(0).context("Failed to parse revisions")?;
+    Ok(revisions)
+}
+
+/// One revision of a memory, or `None` if it doesn't exist or belongs to
+/// another memory.
+pub async fn get_revision(db: &Db, memory_id: &str, revision_id: &str) -> Result<Option<Revision>> {
+    let revision: Option<Revision> = db
+        .select(("memory_revision", revision_id))
+        .await
+        .context("Failed to fetch revision")?;
+
+    Ok(revision.filter(|r| r.memory == memory_id))
+}
+
+/// A generated profile to store
+pub struct NewProfileSnapshot {
+    pub collection: String,
+    pub profile: String,
+    pub model: String,
+    pub strategy: Strategy,
+    pub tags: Vec<String>,
+    pub memories: Vec<String>,
+    pub chunks: usize,
+    pub incremental: bool,
+    pub previous: Option<String>,
+    pub created_at: Datetime,
+}
+
+pub async fn store_profile_snapshot(db: &Db, snapshot: NewProfileSnapshot) -> Result<ProfileSnapshot> {
+    let mut result = db
+        .query("CREATE profile_snapshot SET collection = $collection, profile = $profile, model = $model, strategy = $strategy, tags = $tags, memories = $memories, chunks = $chunks, incremental = $incremental, previous = $previous, created_at = $created_at")
+        .bind(("collection", snapshot.collection))
+        .bind(("profile", snapshot.profile))
+        .bind(("model", snapshot.model))
+        .bind(("strategy", snapshot.strategy))
+        .bind(("tags", snapshot.tags))
+        .bind(("memories", snapshot.memories))
+        .bind(("chunks", snapshot.chunks))
+        .bind(("incremental", snapshot.incremental))
+        .bind(("previous", snapshot.previous))
+        .bind(("created_at", snapshot.created_at))
+        .await
+        .context("Failed to store profile")?;
+
+    let stored: Option<ProfileSnapshot> = result.take(0).context("Failed to parse stored profile")?;
+    stored.context("No profile returned after storing it")
+}
+
+/// The newest profile of a collection generated with these options, if any.
+pub async fn latest_profile_snapshot(
+    db: &Db,
+    collection: &str,
+    strategy: Strategy,
+    tags: &[String],
+) -> Result<Option<ProfileSnapshot>> {
+    let mut result = db
+        .query("SELECT * FROM profile_snapshot WHERE collection = $collection AND strategy = $strategy AND tags = $tags ORDER BY created_at DESC LIMIT 1")
+        .bind(("collection", collection.to_string()))
+        .bind(("strategy", strategy))
+        .bind(("tags", tags.to_vec()))
+        .await
+        .context("Failed to query profiles")?;
+
+    let snapshots: Vec<ProfileSnapshot> = result.take(0).context("Failed to parse profiles")?;
+    Ok(snapshots.into_iter().next())
+}
+
+/// A collection's stored profiles, newest first.
+pub async fn get_profile_snapshots(
+    db: &Db,
+    collection: &str,
+    page: usize,
+    per_page: usize,
+) -> Result<Vec<ProfileSnapshot>> {
+    let mut result = db
+        .query("SELECT * FROM profile_snapshot WHERE collection = $collection ORDER BY created_at DESC LIMIT $limit START $offset")
+        .bind(("collection", collection.to_string()))
+        .bind(("limit", per_page))
+        .bind(("offset", page.saturating_sub(1) * per_page))
+        .await
+        .context("Failed to query profiles")?;
+
+    let snapshots: Vec<ProfileSnapshot> = result.take(0).context("Failed to parse profiles")?;
+    Ok(snapshots)
+}
+
+pub async fn get_profile_snapshot(db: &Db, collection: &str, id: &str) -> Result<Option<ProfileSnapshot>> {
+    let mut result = db
+        .query("SELECT * FROM type::thing('profile_snapshot', $id) WHERE collection = $collection")
+        .bind(("id", id.to_string()))
+        .bind(("collection", collection.to_string()))
+        .await
+        .context("Failed to fetch profile")?;
+
+    let snapshots: Vec<ProfileSnapshot> = result.take(0).context("Failed to parse profile")?;
+    Ok(snapshots.into_iter().next())
+}
+
+/// Give memories stored before history was kept a first revision holding
+/// their current content, so there is something to revert to.
+async fn backfill_revisions(db: &Db) -> Result<()> {
+    #[derive(serde::Deserialize)]
+    struct CountResult {
+        count: usize,
+    }
+
+    let mut result = db
+        .query("SELECT count() FROM memory_revision GROUP ALL")
+        .await
+        .context("Failed to count revisions")?;
+    let count: Option<CountResult> = result.take(0).ok().and_then(|v: Vec<CountResult>| v.into_iter().next());
+    if count.is_some_and(|c| c.count > 0) {
+        return Ok(());
+    }
+
+    db.query("INSERT INTO memory_revision (SELECT record::id(id) AS memory, collection, 'snapshot' AS action, text, tags, source, updated_at AS created_at FROM memory)")
+        .await
+        .context("Failed to backfill revisions")?
+        .check()
+        .context("Failed to backfill revisions")?;
+    Ok(())
+}
+
+/// Memories in a collection, oldest first, for exporting in pages. Trashed
+/// memories are left out.
+pub async fn get_memories_page(
+    db: &Db,
+    collection: &str,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<Memory>> {
+    let mut result = db
+        .query("SELECT * FROM memory WHERE collection = $collection AND deleted_at IS NONE ORDER BY created_at, id LIMIT $limit START $offset")
+        .bind(("collection", collection.to_string()))
+        .bind(("limit", limit))
+        .bind(("offset", offset))
+        .await
+        .context("Failed to query memories")?;
+
+    let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
     Ok(memories)
 }
 
@@ -76,61 +1058,114 @@ pub async fn get_memories_paginated(
     db: &Db,
     page: usize,
     per_page: usize,
-    tag: Option<&str>,
-    source: Option<&str>,
+    filter: &MemoryFilter,
 ) -> Result<Vec<Memory>> {
     let offset = (page.saturating_sub(1)) * per_page;
+    let sql = format!(
+        "SELECT * FROM memory{} ORDER BY created_at DESC LIMIT $limit START $offset",
+        filter.where_clause(None)
+    );
 
-    let query = match (tag, source) {
-        (Some(t), Some(s)) => {
-            db.query("SELECT * FROM memory WHERE $tag IN tags AND source = $source ORDER BY created_at DESC LIMIT $limit START $offset")
-                .bind(("tag", t.to_string()))
-                .bind(("source", s.to_string()))
-                .bind(("limit", per_page))
-                .bind(("offset", offset))
-                .await
-        }
-        (Some(t), None) => {
-            db.query("SELECT * FROM memory WHERE $tag IN tags ORDER BY created_at DESC LIMIT $limit START $offset")
-                .bind(("tag", t.to_string()))
-                .bind(("limit", per_page))
-                .bind(("offset", offset))
-                .await
-        }
-        (None, Some(s)) => {
-            db.query("SELECT * FROM memory WHERE source = $source ORDER BY created_at DESC LIMIT $limit START $offset")
-                .bind(("source", s.to_string()))
-                .bind(("limit", per_page))
-                .bind(("offset", offset))
-                .await
-        }
-        (None, None) => {
-            db.query("SELECT * FROM memory ORDER BY created_at DESC LIMIT $limit START $offset")
-                .bind(("limit", per_page))
-                .bind(("offset", offset))
-                .await
-        }
-    };
+    let mut result = filter
+        .bind(db.query(sql).bind(("limit", per_page)).bind(("offset", offset)))
+        .await
+        .context("Failed to query memories")?;
 
-    let mut result = query.context("Failed to query memories")?;
     let memories: Vec<Memory> = result.take(0).context("Failed to parse memories")?;
     Ok(memories)
 }
 
-pub async fn delete_memory(db: &Db, id: &str) -> Result<Option<Memory>> {
+/// Move a memory to the trash. It keeps its content and can be restored
+/// until the trash is purged.
+pub async fn delete_memory(
+    db: &Db,
+    collection: &str,
+    id: &str,
+    actor: Option<&str>,
+) -> Result<Option<Memory>> {
     let mut result = db
-        .query("DELETE FROM memory WHERE id = $id RETURN BEFORE")
-        .bind(("id", format!("memory:{}", id)))
+        .query("UPDATE type::thing('memory', $id) SET deleted_at = time::now(), updated_by = $actor WHERE collection = $collection AND deleted_at IS NONE RETURN AFTER")
+        .bind(("id", id.to_string()))
+        .bind(("collection", collection.to_string()))
+        .bind(("actor", actor.map(String::from)))
         .await
         .context("Failed to delete memory")?;
 
-    let deleted: Vec<Memory> = result.take(0).unwrap_or_default();
+    let deleted: Vec<Memory> = result.take(0).context("Failed to parse deleted memory")?;
     Ok(deleted.into_iter().next())
 }
 
-pub async fn count_memories(db: &Db) -> Result<usize> {
+/// Take a memory out of the trash, or return `None` if it isn't in it.
+pub async fn restore_memory(
+    db: &Db,
+    collection: &str,
+    id: &str,
+    actor: Option<&str>,
+) -> Result<Option<Memory>> {
+    let mut result = db
+        .query("UPDATE type::thing('memory', $id) SET deleted_at = NONE, updated_by = $actor WHERE collection = $collection AND deleted_at IS NOT NONE RETURN AFTER")
+        .bind(("id", id.to_string()))
+        .bind(("collection", collection.to_string()))
+        .bind(("actor", actor.map(String::from)))
+        .await
+        .context("Failed to restore memory")?;
+
+    let restored: Vec<Memory> = result.take(0).context("Failed to parse restored memory")?;
+    Ok(restored.into_iter().next())
+}
+
+/// Permanently delete memories trashed at or before `cutoff`, returning how
+/// many were removed.
+pub async fn purge_trash(db: &Db, collection: &str, cutoff: Datetime) -> Result<usize> {
+    let mut result = db
+        .query("DELETE memory WHERE collection = $collection AND deleted_at IS NOT NONE AND deleted_at <= $cutoff RETURN BEFORE")
+        .bind(("collection", collection.to_string()))
+        .bind(("cutoff", cutoff))
+        .await
+        .context("Failed to purge trash")?;
+
+    let purged: Vec<MemoryText> = result.take(0).context("Failed to parse purged memories")?;
+    Ok(purged.len())
+}
+
+/// Memories matching `filter`.
+pub async fn count_filtered(db: &Db, filter: &MemoryFilter) -> Result<usize> {
+    let sql = format!("SELECT count() FROM memory{} GROUP ALL", filter.where_clause(None));
+
+    let mut result = filter.bind(db.query(sql)).await.context("Failed to count memories")?;
+
+    #[derive(serde::Deserialize)]
+    struct CountResult {
+        count: usize,
+    }
+
+    let count: Vec<CountResult> = result.take(0).context("Failed to parse memory count")?;
+    Ok(count.first().map_or(0, |c| c.count))
+}
+
+/// Those of `ids` that are still active: not archived, trashed or purged.
+pub async fn active_memory_ids(db: &Db, ids: &[String]) -> Result<HashSet<String>> {
+    let things: Vec<Thing> = ids.iter().map(|id| Thing::from(("memory", id.as_str()))).collect();
+    let mut result = db
+        .query("SELECT VALUE record::id(id) FROM $ids WHERE archived_at IS NONE AND deleted_at IS NONE")
+        .bind(("ids", things))
+        .await
+        .context("Failed to look up memories")?;
+
+    let active: Vec<String> = result.take(0).context("Failed to parse memory ids")?;
+    Ok(active.into_iter().collect())
+}
+
+/// Memories in a collection in the given state.
+pub async fn count_memories(db: &Db, collection: &str, state: MemoryState) -> Result<usize> {
+    let sql = format!(
+        "SELECT count() FROM memory WHERE collection = $collection AND {} GROUP ALL",
+        state.condition()
+    );
+
     let mut result = db
-        .query("SELECT count() FROM memory GROUP ALL")
+        .query(sql)
+        .bind(("collection", collection.to_string()))
         .await
         .context("Failed to count memories")?;
 
@@ -143,28 +1178,43 @@ pub async fn count_memories(db: &Db) -> Result<usize> {
     Ok(count.map(|c| c.count).unwrap_or(0))
 }
 
-pub async fn get_all_texts(db: &Db) -> Result<Vec<String>> {
-    let memories = get_all_memories(db).await?;
-    let mut texts: Vec<String> = memories.into_iter().map(|m| m.text).collect();
-    texts.truncate(100);
-    Ok(texts)
+/// How many active memories in a collection have each tag, most used first.
+/// Counted in the database, so no memory is loaded.
+pub async fn get_tag_counts(db: &Db, collection: &str) -> Result<Vec<(String, usize)>> {
+    tag_counts(db, collection, None).await
 }
 
-pub async fn get_tag_counts(db: &Db) -> Result<Vec<(String, usize)>> {
-    let memories = get_all_memories(db).await?;
-    let mut tag_map: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-    for m in &memories {
-        for tag in &m.tags {
-            *tag_map.entry(tag.clone()).or_insert(0) += 1;
-        }
+/// The `limit` tags most used by active memories in a collection, most used
+/// first.
+pub async fn get_top_tags(db: &Db, collection: &str, limit: usize) -> Result<Vec<String>> {
+    let counts = tag_counts(db, collection, Some(limit)).await?;
+    Ok(counts.into_iter().map(|(tag, _)| tag).collect())
+}
+
+async fn tag_counts(db: &Db, collection: &str, limit: Option<usize>) -> Result<Vec<(String, usize)>> {
+    #[derive(serde::Deserialize)]
+    struct TagCount {
+        tag: String,
+        uses: usize,
     }
-    let mut counts: Vec<(String, usize)> = tag_map.into_iter().collect();
-    counts.sort_by(|a, b| b.1.cmp(&a.1));
-    Ok(counts)
+
+    let sql = format!(
+        "SELECT * FROM (SELECT tag, count() AS uses FROM (SELECT tags AS tag FROM memory WHERE collection = $collection AND archived_at IS NONE AND deleted_at IS NONE AND tags != [] SPLIT tag) GROUP BY tag) ORDER BY uses DESC{}",
+        if limit.is_some() { " LIMIT $limit" } else { "" }
+    );
+    let mut result = db
+        .query(sql)
+        .bind(("collection", collection.to_string()))
+        .bind(("limit", limit))
+        .await
+        .context("Failed to count tags")?;
+
+    let counts: Vec<TagCount> = result.take(0).context("Failed to parse tag counts")?;
+    Ok(counts.into_iter().map(|c| (c.tag, c.uses)).collect())
 }
 
-pub async fn get_source_counts(db: &Db) -> Result<Vec<(String, usize)>> {
-    let memories = get_all_memories(db).await?;
+pub async fn get_source_counts(db: &Db, collection: &str) -> Result<Vec<(String, usize)>> {
+    let memories = get_all_memories(db, collection).await?;
     let mut source_map: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
     for m in &memories {
         if let Some(ref src) = m.source {
@@ -175,3 +1225,98 @@ pub async fn get_source_counts(db: &Db) -> Result<Vec<(String, usize)>> {
     counts.sort_by(|a, b| b.1.cmp(&a.1));
     Ok(counts)
 }
+
+/// Collection names end up in URLs, so keep them to a URL-safe alphabet.
+pub fn is_valid_collection_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub async fn collection_exists(db: &Db, name: &str) -> Result<bool> {
+    let collection: Option<Collection> = db
+        .select(("collection", name))
+        .await
+        .context("Failed to fetch collection")?;
+
+    Ok(collection.is_some())
+}
+
+/// Create a collection, or return `None` if one with this name already exists.
+pub async fn create_collection(
+    db: &Db,
+    name: &str,
+    description: Option<String>,
+) -> Result<Option<Collection>> {
+    if collection_exists(db, name).await? {
+        return Ok(None);
+    }
+
+    let mut result = db
+        .query("CREATE type::thing('collection', $name) SET name = $name, description = $description, created_at = time::now()")
+        .bind(("name", name.to_string()))
+        .bind(("description", description))
+        .await
+        .context("Failed to create collection")?;
+
+    let collection: Option<Collection> =
+        result.take(0).context("Failed to parse created collection")?;
+    collection.context("No collection returned after creation").map(Some)
+}
+
+/// Every collection with its number of memories, sorted by name.
+pub async fn list_collections(db: &Db) -> Result<Vec<(Collection, usize)>> {
+    #[derive(serde::Deserialize)]
+    struct CollectionCount {
+        collection: String,
+        count: usize,
+    }
+
+    let mut result = db
+        .query(
+            "SELECT * FROM collection ORDER BY name;
+             SELECT collection, count() FROM memory WHERE archived_at IS NONE AND deleted_at IS NONE GROUP BY collection;",
+        )
+        .await
+        .context("Failed to list collections")?;
+
+    let collections: Vec<Collection> = result.take(0).context("Failed to parse collections")?;
+    let counts: Vec<CollectionCount> = result.take(1).context("Failed to count memories")?;
+    let counts: std::collections::HashMap<String, usize> =
+        counts.into_iter().map(|c| (c.collection, c.count)).collect();
+
+    Ok(collections
+        .into_iter()
+        .map(|c| {
+            let count = counts.get(&c.name).copied().unwrap_or(0);
+            (c, count)
+        })
+        .collect())
+}
+
+/// Delete a collection and all of its memories, returning how many memories
+/// were removed, or `None` if the collection doesn't exist.
+pub async fn delete_collection(db: &Db, name: &str) -> Result<Option<usize>> {
+    if !collection_exists(db, name).await? {
+        return Ok(None);
+    }
+
+    let mut result = db
+        .query(
+            "BEGIN TRANSACTION;
+             DELETE memory WHERE collection = $name RETURN BEFORE;
+             DELETE profile_snapshot WHERE collection = $name;
+             DELETE type::thing('collection', $name);
+             COMMIT TRANSACTION;",
+        )
+        .bind(("name", name.to_string()))
+        .await
+        .context("Failed to delete collection")?
+        .check()
+        .context("Failed to delete collection")?;
+
+    let deleted: Vec<MemoryText> = result.take(0).context("Failed to parse deleted memories")?;
+    Ok(Some(deleted.len()))
+}
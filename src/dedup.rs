@@ -0,0 +1,145 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db::{self, Db, MemoryFilter};
+use crate::embeddings::cosine_similarity;
+use crate::models::{CreateMemoryRequest, Memory};
+
+/// Nearest neighbours compared against a new memory
+const NEIGHBOURS: usize = 5;
+
+/// What to do when a new memory duplicates an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupPolicy {
+    /// Refuse to store the new memory
+    Reject,
+    /// Add the new memory's tags to the existing one and bump its `updated_at`
+    Merge,
+    /// Store the new memory anyway
+    Allow,
+}
+
+impl std::fmt::Display for DedupPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Reject => "reject",
+            Self::Merge => "merge",
+            Self::Allow => "allow",
+        })
+    }
+}
+
+impl std::str::FromStr for DedupPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "reject" => Ok(Self::Reject),
+            "merge" => Ok(Self::Merge),
+            "allow" => Ok(Self::Allow),
+            other => anyhow::bail!("Unknown dedup policy '{}' (expected reject, merge or allow)", other),
+        }
+    }
+}
+
+/// The configured policy and the similarity above which memories count as
+/// near-duplicates.
+#[derive(Debug, Clone, Copy)]
+pub struct DedupSettings {
+    pub policy: DedupPolicy,
+    pub threshold: f32,
+}
+
+/// Hash identifying memories with the same text, ignoring case and whitespace.
+pub fn content_hash(text: &str) -> String {
+    let normalized = text
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// Result of storing a memory under a dedup policy.
+pub enum Written {
+    Created(Memory),
+    /// Merged into this existing memory
+    Merged(Memory, f32),
+    /// Not stored because of this existing memory
+    Rejected(Memory, f32),
+}
+
+/// Store a memory unless it duplicates one already in the collection, in
+/// which case the request's policy (or the configured one) decides.
+pub async fn create_memory(
+    db: &Db,
+    collection: &str,
+    req: CreateMemoryRequest,
+    embedding: Vec<f32>,
+    embed_model: &str,
+    settings: DedupSettings,
+    actor: Option<&str>,
+) -> Result<Written> {
+    let policy = req.dedup.unwrap_or(settings.policy);
+
+    if policy != DedupPolicy::Allow {
+        let duplicate =
+            find_duplicate(db, collection, &req.text, &embedding, embed_model, settings.threshold)
+                .await?;
+
+        if let Some((existing, similarity)) = duplicate {
+            if policy == DedupPolicy::Reject {
+                return Ok(Written::Rejected(existing, similarity));
+            }
+
+            let id = existing.id.as_ref().map(|t| t.id.to_string()).unwrap_or_default();
+            let merged = db::merge_memory(db, &id, req.tags, req.auto_tags, req.source, actor).await?;
+            return Ok(Written::Merged(merged, similarity));
+        }
+    }
+
+    let memory = db::create_memory(db, collection, req, embedding, embed_model, actor).await?;
+    Ok(Written::Created(memory))
+}
+
+/// The existing memory with the same text (similarity 1.0), or else the most
+/// similar of the nearest neighbours if it reaches `threshold`.
+async fn find_duplicate(
+    db: &Db,
+    collection: &str,
+    text: &str,
+    embedding: &[f32],
+    embed_model: &str,
+    threshold: f32,
+) -> Result<Option<(Memory, f32)>> {
+    if let Some(memory) = db::find_by_content_hash(db, collection, &content_hash(text)).await? {
+        return Ok(Some((memory, 1.0)));
+    }
+
+    // Vectors from another model aren't comparable with the new one
+    let filter = MemoryFilter {
+        collection: Some(collection.to_string()),
+        embed_model: Some(embed_model.to_string()),
+        ..Default::default()
+    };
+
+    let neighbours = match db::search_nearest(db, embedding.to_vec(), NEIGHBOURS, &filter).await {
+        Ok(neighbours) => neighbours,
+        Err(err) => {
+            // The index is dropped while `reembed` rebuilds it
+            tracing::warn!("Vector index unavailable, skipping near-duplicate check: {:#}", err);
+            return Ok(None);
+        }
+    };
+
+    Ok(neighbours
+        .into_iter()
+        .map(|m| {
+            let similarity = cosine_similarity(embedding, &m.embedding);
+            (m, similarity)
+        })
+        .filter(|(_, similarity)| *similarity >= threshold)
+        .max_by(|a, b| a.1.total_cmp(&b.1)))
+}
@@ -1,16 +1,130 @@
+use std::path::Path;
+
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future;
 use reqwest::Client;
 
 use crate::config::Config;
-use crate::models::{OllamaEmbedRequest, OllamaEmbedResponse};
+use crate::models::{OllamaEmbedRequest, OllamaEmbedResponse, OpenAiEmbedRequest, OpenAiEmbedResponse};
+
+/// A backend that turns text into embedding vectors.
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// Embed several texts, returning vectors in input order. Providers with a
+    /// batch API should override this to send a single request.
+    async fn embed_many(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed(text).await?);
+        }
+        Ok(embeddings)
+    }
+}
 
 pub struct EmbeddingClient {
+    provider: Box<dyn EmbeddingProvider>,
+    model: String,
+    batch_size: usize,
+    concurrency: usize,
+}
+
+impl EmbeddingClient {
+    pub fn new(config: &Config) -> Result<Self> {
+        let provider: Box<dyn EmbeddingProvider> = match config.embed_provider.as_str() {
+            "ollama" => Box::new(OllamaProvider::new(config)),
+            "openai" => Box::new(OpenAiProvider::new(config)),
+            "hash" => Box::new(HashProvider::new(config.embed_dim)),
+            "local" => local_provider(config.embed_model_path.as_deref())?,
+            other => anyhow::bail!(
+                "Unknown embedding provider '{}' (expected ollama, openai, local or hash)",
+                other
+            ),
+        };
+
+        Ok(Self {
+            provider,
+            model: config.embedding_model(),
+            batch_size: config.embed_batch_size.max(1),
+            concurrency: config.embed_concurrency.max(1),
+        })
+    }
+
+    /// Model name recorded alongside the embeddings this client produces
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.provider.embed(text).await
+    }
+
+    /// Embed many texts in batches of `batch_size`, with up to `concurrency`
+    /// requests in flight. Returns one result per text, in input order; when a
+    /// request fails, every text in its batch gets the error.
+    pub async fn embed_batch(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        let chunks: Vec<&[String]> = texts.chunks(self.batch_size).collect();
+
+        let mut results = Vec::with_capacity(texts.len());
+        for wave in chunks.chunks(self.concurrency) {
+            let requests = wave.iter().map(|chunk| self.provider.embed_many(chunk));
+            let batches = future::join_all(requests).await;
+
+            for (chunk, batch) in wave.iter().zip(batches) {
+                let batch = batch.and_then(|embeddings| {
+                    if embeddings.len() != chunk.len() {
+                        anyhow::bail!(
+                            "Expected {} embeddings, got {}",
+                            chunk.len(),
+                            embeddings.len()
+                        );
+                    }
+                    Ok(embeddings)
+                });
+
+                match batch {
+                    Ok(embeddings) => results.extend(embeddings.into_iter().map(Ok)),
+                    Err(err) => {
+                        let msg = format!("{:#}", err);
+                        results.extend(chunk.iter().map(|_| Err(anyhow::anyhow!(msg.clone()))));
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+#[cfg(feature = "local-embeddings")]
+fn local_provider(path: Option<&Path>) -> Result<Box<dyn EmbeddingProvider>> {
+    let path =
+        path.context("MEMORAI_EMBED_MODEL_PATH must be set for the local embedding provider")?;
+    Ok(Box::new(crate::local_embeddings::LocalProvider::load(path)?))
+}
+
+#[cfg(not(feature = "local-embeddings"))]
+fn local_provider(_path: Option<&Path>) -> Result<Box<dyn EmbeddingProvider>> {
+    anyhow::bail!("memorai was built without the `local-embeddings` feature")
+}
+
+/// Ollama's `/api/embed` endpoint.
+pub struct OllamaProvider {
     client: Client,
     ollama_url: String,
     model: String,
 }
 
-impl EmbeddingClient {
+impl OllamaProvider {
     pub fn new(config: &Config) -> Self {
         Self {
             client: Client::new(),
@@ -18,12 +132,23 @@ impl EmbeddingClient {
             model: config.embed_model.clone(),
         }
     }
+}
 
-    pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+#[async_trait]
+impl EmbeddingProvider for OllamaProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_many(&[text.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .context("No embedding returned from Ollama")
+    }
+
+    async fn embed_many(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         let url = format!("{}/api/embed", self.ollama_url);
         let request = OllamaEmbedRequest {
             model: self.model.clone(),
-            input: text.to_string(),
+            input: texts.to_vec(),
         };
 
         let response = self
@@ -45,12 +170,114 @@ impl EmbeddingClient {
             .await
             .context("Failed to parse Ollama embedding response")?;
 
-        embed_response
-            .embeddings
+        Ok(embed_response.embeddings)
+    }
+}
+
+/// Any server implementing OpenAI's `/v1/embeddings` (llama.cpp, vLLM, LM Studio, ...).
+pub struct OpenAiProvider {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiProvider {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: config.embed_url.trim_end_matches('/').to_string(),
+            api_key: config.embed_api_key.clone(),
+            model: config.embed_model.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_many(&[text.to_string()])
+            .await?
             .into_iter()
             .next()
-            .context("No embedding returned from Ollama")
+            .context("No embedding returned from embeddings server")
     }
+
+    async fn embed_many(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = format!("{}/v1/embeddings", self.base_url);
+        let request = OpenAiEmbedRequest {
+            model: self.model.clone(),
+            input: texts.to_vec(),
+        };
+
+        let mut builder = self.client.post(&url).json(&request);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        let response = builder
+            .send()
+            .await
+            .context("Failed to connect to embeddings server")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Embedding request failed ({}): {}", status, body);
+        }
+
+        let mut embed_response: OpenAiEmbedResponse = response
+            .json()
+            .await
+            .context("Failed to parse embedding response")?;
+
+        embed_response.data.sort_by_key(|d| d.index);
+        Ok(embed_response.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+/// Deterministic feature-hashing embeddings. Needs no model or network, which
+/// makes it useful for tests and offline use, but it only captures shared words.
+pub struct HashProvider {
+    dim: usize,
+}
+
+impl HashProvider {
+    pub fn new(dim: usize) -> Self {
+        Self { dim: dim.max(1) }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut vector = vec![0.0f32; self.dim];
+
+        for token in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+        {
+            let hash = fnv1a(&token.to_lowercase());
+            let slot = (hash % self.dim as u64) as usize;
+            // Use a separate bit for the sign so collisions tend to cancel out
+            let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+            vector[slot] += sign;
+        }
+
+        let norm: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+
+        Ok(vector)
+    }
+}
+
+/// FNV-1a, chosen because it is stable across Rust versions unlike `DefaultHasher`.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
 }
 
 /// Compute cosine similarity between two vectors
@@ -0,0 +1,318 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::db::{self, Db};
+use crate::embeddings::EmbeddingClient;
+use crate::models::{DocumentStatus, IngestDocumentRequest, IngestDocumentResponse};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 1500;
+pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+
+/// Largest document request accepted, well above the default JSON body limit
+pub const MAX_DOCUMENT_BYTES: usize = 8 * 1024 * 1024;
+
+/// Split a document into chunks and store them as memories with the document
+/// as their source, replacing those from an earlier ingest. Nothing is
+/// embedded when the document and options are unchanged since then.
+pub async fn ingest_document(
+    db: &Db,
+    embeddings: &EmbeddingClient,
+    collection: &str,
+    embed_dim: usize,
+    req: IngestDocumentRequest,
+    actor: Option<&str>,
+) -> Result<IngestDocumentResponse> {
+    let chunk_size = req.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    let overlap = req.chunk_overlap.unwrap_or(DEFAULT_CHUNK_OVERLAP);
+    if chunk_size < 100 {
+        anyhow::bail!("chunk_size must be at least 100");
+    }
+    if overlap >= chunk_size / 2 {
+        anyhow::bail!("chunk_overlap must be less than half of chunk_size");
+    }
+
+    let document = Document::parse(&req, chunk_size, overlap);
+    let previous = db::document_hashes(db, collection, &req.source).await?;
+
+    if !previous.is_empty() && previous.iter().all(|h| h.as_deref() == Some(document.hash.as_str())) {
+        return Ok(IngestDocumentResponse {
+            source: req.source,
+            status: DocumentStatus::Unchanged,
+            chunks: previous.len(),
+        });
+    }
+
+    let mut chunks = Vec::with_capacity(document.chunks.len());
+    let results = embeddings.embed_batch(&document.chunks).await;
+    for (text, embedding) in document.chunks.into_iter().zip(results) {
+        let embedding = embedding?;
+        if embedding.len() != embed_dim {
+            anyhow::bail!(
+                "model returned {} dimensions but MEMORAI_EMBED_DIM is {}",
+                embedding.len(),
+                embed_dim
+            );
+        }
+        chunks.push((text, embedding));
+    }
+
+    let count = chunks.len();
+    let stored = db::DocumentChunks {
+        hash: document.hash,
+        tags: document.tags,
+        chunks,
+        embed_model: embeddings.model().to_string(),
+    };
+    db::replace_document(db, collection, &req.source, stored, actor).await?;
+
+    Ok(IngestDocumentResponse {
+        source: req.source,
+        status: if previous.is_empty() {
+            DocumentStatus::Created
+        } else {
+            DocumentStatus::Updated
+        },
+        chunks: count,
+    })
+}
+
+/// A document split into memories, with the tags to store on each chunk.
+struct Document {
+    chunks: Vec<String>,
+    tags: Vec<String>,
+    /// Hash of the content and every option that affects the chunks, so a
+    /// re-ingest can tell whether anything would change
+    hash: String,
+}
+
+impl Document {
+    fn parse(req: &IngestDocumentRequest, chunk_size: usize, overlap: usize) -> Self {
+        let (front_matter, body) = if req.markdown {
+            split_front_matter(&req.text)
+        } else {
+            (None, req.text.as_str())
+        };
+
+        let mut tags = req.tags.clone();
+        for tag in front_matter.map(front_matter_tags).unwrap_or_default() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let sections = if req.markdown {
+            split_sections(body)
+        } else {
+            vec![body.to_string()]
+        };
+        let chunks = sections
+            .iter()
+            .flat_map(|section| split_section(section, chunk_size, overlap))
+            .collect();
+
+        let hash = Sha256::new()
+            .chain_update(format!(
+                "{}:{}:{}:{}\n",
+                chunk_size,
+                overlap,
+                req.markdown,
+                req.tags.join(",")
+            ))
+            .chain_update(&req.text)
+            .finalize();
+
+        Self {
+            chunks,
+            tags,
+            hash: format!("{:x}", hash),
+        }
+    }
+}
+
+/// Separate YAML front matter (between `---` lines at the very start) from the body.
+fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (None, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+/// Tags from front matter, in any of the forms Obsidian accepts:
+/// `tags: [a, b]`, `tags: a, b` or a `- a` list on the following lines.
+fn front_matter_tags(front_matter: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_list = false;
+
+    for line in front_matter.lines() {
+        if in_list {
+            if let Some(item) = line.trim_start().strip_prefix("- ") {
+                tags.push(item.to_string());
+                continue;
+            }
+            if line.starts_with([' ', '\t']) || line.trim().is_empty() {
+                continue;
+            }
+            in_list = false;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if !matches!(key.trim(), "tags" | "tag") {
+            continue;
+        }
+
+        let value = value.trim();
+        if value.is_empty() {
+            in_list = true;
+        } else {
+            let value = value.trim_start_matches('[').trim_end_matches(']');
+            tags.extend(value.split([',', ' ']).map(str::to_string));
+        }
+    }
+
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_matches(['"', '\'']).trim_start_matches('#').to_string();
+        if !tag.is_empty() && !cleaned.contains(&tag) {
+            cleaned.push(tag);
+        }
+    }
+    cleaned
+}
+
+/// Split Markdown at headings, ignoring `#` lines inside code blocks. Each
+/// section starts with its heading; sections with no content are dropped.
+fn split_sections(body: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    let mut current = String::new();
+    let mut fence: Option<&str> = None;
+
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => {}
+            None if trimmed.starts_with("```") => fence = Some("```"),
+            None if trimmed.starts_with("~~~") => fence = Some("~~~"),
+            None if is_heading(line) => {
+                sections.push(std::mem::take(&mut current));
+            }
+            None => {}
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    sections.push(current);
+
+    sections
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| s.lines().any(|line| !line.trim().is_empty() && !is_heading(line)))
+        .collect()
+}
+
+fn is_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+}
+
+/// Split a section into chunks of at most `chunk_size` characters, breaking
+/// between paragraphs where possible and between words otherwise. Each chunk
+/// after the first starts with the section's heading and, when it fits, the
+/// last `overlap` characters of the chunk before.
+fn split_section(section: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    if chars(section) <= chunk_size {
+        return vec![section.to_string()];
+    }
+
+    let (prefix, body) = match section.split_once('\n') {
+        Some((first, rest)) if is_heading(first) && chars(first) * 2 < chunk_size => {
+            (format!("{}\n\n", first), rest.trim())
+        }
+        _ => (String::new(), section),
+    };
+    let budget = chunk_size - chars(&prefix);
+    // Leave room for the overlap when a paragraph has to be cut mid-way
+    let piece_size = budget.saturating_sub(overlap + 2).max(budget / 2);
+
+    let mut pieces = Vec::new();
+    for paragraph in body.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if chars(paragraph) <= budget {
+            pieces.push(paragraph.to_string());
+        } else {
+            pieces.extend(split_words(paragraph, piece_size));
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for piece in pieces {
+        if !current.is_empty() && chars(&current) + 2 + chars(&piece) > budget {
+            let carried = tail(&current, overlap);
+            chunks.push(std::mem::take(&mut current));
+            if !carried.is_empty() && chars(&carried) + 2 + chars(&piece) <= budget {
+                current = carried;
+            }
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks.into_iter().map(|chunk| format!("{}{}", prefix, chunk)).collect()
+}
+
+/// Break text with no paragraph breaks into pieces of at most `size` characters
+/// at word boundaries. A single word longer than `size` becomes its own piece.
+fn split_words(text: &str, size: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && chars(&current) + 1 + chars(word) > size {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// The last `count` characters of `text`, starting at a word boundary.
+fn tail(text: &str, count: usize) -> String {
+    let total = chars(text);
+    if count == 0 || total <= count {
+        return String::new();
+    }
+
+    let start = text.char_indices().nth(total - count).map(|(i, _)| i).unwrap_or(0);
+    let rest = &text[start..];
+    match rest.find(char::is_whitespace) {
+        Some(i) => rest[i..].trim().to_string(),
+        None => String::new(),
+    }
+}
+
+fn chars(text: &str) -> usize {
+    text.chars().count()
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Progress of a background job, as reported by `GET /v1/jobs/{id}`.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub kind: String,
+    pub state: JobState,
+    pub total: usize,
+    pub processed: usize,
+    pub failed: usize,
+    pub errors: Vec<String>,
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// Maximum per-item errors kept on a job; the `failed` count keeps going.
+const MAX_JOB_ERRORS: usize = 100;
+
+/// In-memory registry of background jobs. Jobs don't survive a restart.
+#[derive(Clone, Default)]
+pub struct Jobs {
+    inner: Arc<Mutex<HashMap<String, JobStatus>>>,
+}
+
+impl Jobs {
+    /// Register a new running job, or `None` if one of the same kind is
+    /// already running.
+    pub fn start(&self, kind: &str) -> Option<JobStatus> {
+        let mut jobs = self.inner.lock().unwrap();
+        if jobs
+            .values()
+            .any(|j| j.kind == kind && j.state == JobState::Running)
+        {
+            return None;
+        }
+
+        let status = JobStatus {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            state: JobState::Running,
+            total: 0,
+            processed: 0,
+            failed: 0,
+            errors: Vec::new(),
+            error: None,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+        };
+        jobs.insert(status.id.clone(), status.clone());
+        Some(status)
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobStatus> {
+        self.inner.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<JobStatus> {
+        let mut jobs: Vec<JobStatus> = self.inner.lock().unwrap().values().cloned().collect();
+        jobs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        jobs
+    }
+
+    pub fn set_total(&self, id: &str, total: usize) {
+        self.update(id, |j| j.total = total);
+    }
+
+    pub fn record_success(&self, id: &str) {
+        self.update(id, |j| j.processed += 1);
+    }
+
+    pub fn record_failure(&self, id: &str, error: String) {
+        self.update(id, |j| {
+            j.processed += 1;
+            j.failed += 1;
+            if j.errors.len() < MAX_JOB_ERRORS {
+                j.errors.push(error);
+            }
+        });
+    }
+
+    pub fn finish(&self, id: &str, result: anyhow::Result<()>) {
+        self.update(id, |j| {
+            match result {
+                Ok(()) => j.state = JobState::Completed,
+                Err(err) => {
+                    j.state = JobState::Failed;
+                    j.error = Some(format!("{:#}", err));
+                }
+            }
+            j.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        });
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut JobStatus)) {
+        if let Some(job) = self.inner.lock().unwrap().get_mut(id) {
+            f(job);
+        }
+    }
+}
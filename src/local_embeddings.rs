@@ -0,0 +1,105 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config as BertConfig, DTYPE};
+use tokenizers::{Tokenizer, TruncationParams};
+
+use crate::embeddings::EmbeddingProvider;
+
+/// A BERT-style sentence embedding model (e.g. all-MiniLM-L6-v2, bge-small)
+/// running on the CPU inside the memorai process.
+///
+/// The model directory must contain the Hugging Face `config.json`,
+/// `tokenizer.json` and `model.safetensors` files.
+pub struct LocalProvider {
+    model: Arc<LocalModel>,
+}
+
+struct LocalModel {
+    bert: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl LocalProvider {
+    pub fn load(path: &Path) -> Result<Self> {
+        let device = Device::Cpu;
+
+        let config = std::fs::read_to_string(path.join("config.json"))
+            .with_context(|| format!("Failed to read config.json in {}", path.display()))?;
+        let config: BertConfig =
+            serde_json::from_str(&config).context("Failed to parse model config.json")?;
+
+        let mut tokenizer = Tokenizer::from_file(path.join("tokenizer.json"))
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Failed to load tokenizer.json in {}", path.display()))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(anyhow::Error::msg)?;
+        tokenizer.with_padding(None);
+
+        let weights = path.join("model.safetensors");
+        // SAFETY: the weights file is memory-mapped read-only and must not be
+        // modified while memorai is running.
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights], DTYPE, &device) }
+            .with_context(|| format!("Failed to load model.safetensors in {}", path.display()))?;
+        let bert = BertModel::load(vb, &config).context("Failed to build BERT model")?;
+
+        tracing::info!("Loaded local embedding model from {}", path.display());
+
+        Ok(Self {
+            model: Arc::new(LocalModel {
+                bert,
+                tokenizer,
+                device,
+            }),
+        })
+    }
+}
+
+impl LocalModel {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(anyhow::Error::msg)
+            .context("Failed to tokenize text")?;
+
+        let input_ids = Tensor::new(encoding.get_ids(), &self.device)?.unsqueeze(0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+        let attention_mask =
+            Tensor::new(encoding.get_attention_mask(), &self.device)?.unsqueeze(0)?;
+
+        let hidden = self
+            .bert
+            .forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+
+        // Mean pooling over tokens, then L2 normalisation
+        let (_, tokens, _) = hidden.dims3()?;
+        let pooled = (hidden.sum(1)? / tokens as f64)?.squeeze(0)?;
+        let norm = pooled.sqr()?.sum_all()?.sqrt()?;
+        let normalized = pooled.broadcast_div(&norm)?;
+
+        Ok(normalized.to_vec1::<f32>()?)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for LocalProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let model = self.model.clone();
+        let text = text.to_string();
+
+        // Inference is CPU-bound; keep it off the async workers
+        tokio::task::spawn_blocking(move || model.embed(&text))
+            .await
+            .context("Embedding task panicked")?
+    }
+}
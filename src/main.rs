@@ -1,12 +1,27 @@
+mod ask;
+mod autotag;
+mod auth;
+mod chat;
 mod config;
+mod consolidate;
 mod db;
+mod dedup;
 mod embeddings;
+mod ingest;
+mod jobs;
+#[cfg(feature = "local-embeddings")]
+mod local_embeddings;
+mod mcp;
 mod models;
 mod profile;
+mod reembed;
+mod search;
 mod server;
+mod sse;
+mod transfer;
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
 
 use config::Config;
 
@@ -17,14 +32,68 @@ use config::Config;
     about = "Local-first AI memory system with semantic search"
 )]
 struct Cli {
+    /// Collection to operate on (defaults to the `default` collection)
+    #[arg(long, global = true)]
+    collection: Option<String>,
+    /// Config file (defaults to ~/.memorai/config.toml)
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
+    #[command(flatten)]
+    overrides: ConfigOverrides,
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Command-line settings, taking precedence over the environment and config file
+#[derive(Args)]
+struct ConfigOverrides {
+    /// Address the API server binds to
+    #[arg(long, global = true)]
+    host: Option<String>,
+    /// API server port
+    #[arg(long, global = true)]
+    port: Option<String>,
+    /// Database storage path
+    #[arg(long, global = true)]
+    data_dir: Option<String>,
+    /// Server URL for CLI commands
+    #[arg(long, global = true)]
+    url: Option<String>,
+    /// Embedding backend: ollama, openai, local or hash
+    #[arg(long, global = true)]
+    embed_provider: Option<String>,
+    /// Embedding model name
+    #[arg(long, global = true)]
+    embed_model: Option<String>,
+    /// Chat model for profiles
+    #[arg(long, global = true)]
+    chat_model: Option<String>,
+}
+
+impl ConfigOverrides {
+    fn into_map(self) -> std::collections::HashMap<&'static str, String> {
+        [
+            ("host", self.host),
+            ("port", self.port),
+            ("data_dir", self.data_dir),
+            ("url", self.url),
+            ("embed_provider", self.embed_provider),
+            ("embed_model", self.embed_model),
+            ("chat_model", self.chat_model),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| (key, v)))
+        .collect()
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start the memorai API server
     Serve,
+    /// Serve the Model Context Protocol over stdin and stdout, for assistants
+    /// that launch memorai themselves
+    Mcp,
     /// Add a memory
     Add {
         /// The text to remember
@@ -35,54 +104,529 @@ enum Commands {
         /// Source of the memory
         #[arg(short, long)]
         source: Option<String>,
+        /// What to do if it duplicates an existing memory: reject, merge or allow
+        #[arg(long)]
+        dedup: Option<String>,
+        /// Have the chat model suggest tags, even if the memory has some
+        #[arg(long, conflicts_with = "no_auto_tag")]
+        auto_tag: bool,
+        /// Don't suggest tags, even if the server would
+        #[arg(long)]
+        no_auto_tag: bool,
+    },
+    /// Show a single memory
+    Show {
+        /// Memory id
+        id: String,
+        /// Also print the stored embedding
+        #[arg(long)]
+        embedding: bool,
+    },
+    /// Edit an existing memory
+    Edit {
+        /// Memory id
+        id: String,
+        /// New text (the memory is re-embedded when it changes)
+        #[arg(long)]
+        text: Option<String>,
+        /// Comma-separated tags, replacing the existing ones
+        #[arg(short, long)]
+        tags: Option<String>,
+        /// New source
+        #[arg(short, long, conflicts_with = "clear_source")]
+        source: Option<String>,
+        /// Remove the source
+        #[arg(long)]
+        clear_source: bool,
+    },
+    /// Show how a memory has changed, newest revision first
+    History {
+        /// Memory id
+        id: String,
+    },
+    /// Restore a memory's text, tags and source from an earlier revision
+    Revert {
+        /// Memory id
+        id: String,
+        /// Revision id, as shown by `memorai history`
+        revision: String,
+    },
+    /// Move a memory to the trash
+    Delete {
+        /// Memory id
+        id: String,
+    },
+    /// List, restore or purge deleted memories
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
     },
     /// Search memories semantically
     Search {
         /// Search query
         query: String,
-        /// Max results
-        #[arg(short, long, default_value = "5")]
-        limit: usize,
+        #[command(flatten)]
+        options: SearchOptions,
     },
     /// Show memory statistics
     Stats,
     /// Generate a user profile from stored memories
-    Profile,
+    Profile {
+        /// Which memories to use when there are too many: recent, diverse or tag
+        #[arg(long)]
+        strategy: Option<String>,
+        /// Comma-separated tags to build the profile from
+        #[arg(short, long)]
+        tags: Option<String>,
+        /// Most memories to use
+        #[arg(long)]
+        max_memories: Option<usize>,
+        /// Tokens of notes per prompt to the chat model
+        #[arg(long)]
+        token_budget: Option<usize>,
+        /// Write the profile from scratch instead of using the stored one
+        #[arg(long)]
+        refresh: bool,
+        #[command(subcommand)]
+        command: Option<ProfileCommands>,
+    },
+    /// Answer a question from stored memories, citing the ones used
+    Ask {
+        /// The question
+        question: String,
+        /// Memories to retrieve
+        #[arg(short, long)]
+        limit: Option<usize>,
+        /// Similarity a memory needs to be used (defaults to MEMORAI_ASK_MIN_SCORE)
+        #[arg(long)]
+        min_score: Option<f32>,
+        /// Comma-separated tags to restrict the memories to
+        #[arg(short, long)]
+        tags: Option<String>,
+        /// Only memories from this source
+        #[arg(short, long)]
+        source: Option<String>,
+    },
+    /// Re-embed memories created with a different embedding model
+    Reembed {
+        /// Re-embed every memory, even those already using the current model
+        #[arg(long)]
+        all: bool,
+    },
+    /// Ingest Markdown and text files, such as an Obsidian vault
+    Ingest {
+        /// File or directory (searched recursively, skipping hidden folders)
+        path: std::path::PathBuf,
+        /// Comma-separated tags added to every chunk
+        #[arg(short, long)]
+        tags: Option<String>,
+        /// Maximum characters per chunk
+        #[arg(long, default_value_t = ingest::DEFAULT_CHUNK_SIZE)]
+        chunk_size: usize,
+        /// Characters repeated from the end of one chunk at the start of the next
+        #[arg(long, default_value_t = ingest::DEFAULT_CHUNK_OVERLAP)]
+        overlap: usize,
+    },
+    /// Export memories as JSONL
+    Export {
+        /// File to write (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+        /// Include embeddings, so an import with the same model needn't recompute them
+        #[arg(long)]
+        embeddings: bool,
+    },
+    /// Import memories from a JSONL export
+    Import {
+        /// File to read
+        file: std::path::PathBuf,
+        /// Replace memories whose id already exists instead of skipping them
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Merge clusters of related memories into summaries written by the chat model
+    Consolidate {
+        /// Similarity needed to join a cluster (0-1)
+        #[arg(long)]
+        threshold: Option<f32>,
+        /// Largest number of memories merged into one
+        #[arg(long)]
+        max_cluster_size: Option<usize>,
+        /// Archive the originals, leaving them out of search and listings
+        #[arg(long)]
+        archive: bool,
+    },
+    /// Manage collections
+    Collections {
+        #[command(subcommand)]
+        command: CollectionCommands,
+    },
+    /// Manage API keys (edits the keys file in the data directory)
+    Keys {
+        #[command(subcommand)]
+        command: KeyCommands,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective configuration and where each value came from
+    Show,
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// List memories in the trash, most recently created first
+    List {
+        #[arg(long, default_value = "1")]
+        page: usize,
+        #[arg(long, default_value = "20")]
+        per_page: usize,
+    },
+    /// Take a memory out of the trash
+    Restore {
+        /// Memory id
+        id: String,
+    },
+    /// Permanently delete memories that have been in the trash for a while
+    Purge {
+        /// Days in the trash before a memory is purged (defaults to
+        /// MEMORAI_TRASH_RETENTION_DAYS; 0 empties the trash)
+        #[arg(long)]
+        older_than_days: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// List stored profiles, newest first
+    History {
+        #[arg(long, default_value = "1")]
+        page: usize,
+        #[arg(long, default_value = "20")]
+        per_page: usize,
+    },
+    /// Show a stored profile and what changed since the one before it
+    Show {
+        /// Profile id, as shown by `memorai profile history`
+        id: String,
+        /// Compare with this profile instead
+        #[arg(long)]
+        against: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CollectionCommands {
+    /// List collections and their memory counts
+    List,
+    /// Create a collection
+    Create {
+        /// Collection name (letters, digits, '-' and '_')
+        name: String,
+        /// What the collection is for
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+    /// Delete a collection and all of its memories
+    Delete {
+        /// Collection name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyCommands {
+    /// Create a key and print its secret
+    Create {
+        /// Name to recognise the key by
+        name: String,
+        /// Comma-separated scopes: read, write, admin (each includes the ones before it)
+        #[arg(short, long, default_value = "write")]
+        scopes: String,
+        /// Comma-separated collections to restrict the key to
+        #[arg(short, long)]
+        collections: Option<String>,
+    },
+    /// List keys
+    List,
+    /// Revoke a key
+    Revoke {
+        /// Key id, as shown by `memorai keys list`
+        id: String,
+    },
+}
+
+#[derive(Args)]
+struct SearchOptions {
+    /// Max results
+    #[arg(short, long, default_value = "5")]
+    limit: usize,
+    /// Score every memory exactly instead of using the vector index
+    #[arg(long)]
+    exact: bool,
+    /// Ranking mode: vector, keyword or hybrid
+    #[arg(short, long, default_value = "vector")]
+    mode: String,
+    /// Comma-separated tags to filter on
+    #[arg(short, long)]
+    tags: Option<String>,
+    /// Require all of --tags instead of any
+    #[arg(long)]
+    all_tags: bool,
+    /// Only memories from this source
+    #[arg(short, long)]
+    source: Option<String>,
+    /// Only memories created on or after this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long)]
+    after: Option<String>,
+    /// Only memories created on or before this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long)]
+    before: Option<String>,
+    /// Drop results scoring below this (in hybrid mode, vector matches below
+    /// this similarity)
+    #[arg(long)]
+    min_score: Option<f32>,
+}
+
+impl SearchOptions {
+    fn to_params(&self, query: &str) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("q", query.to_string()),
+            ("limit", self.limit.to_string()),
+            ("mode", self.mode.clone()),
+        ];
+        if self.exact {
+            params.push(("exact", "true".to_string()));
+        }
+        if let Some(tags) = &self.tags {
+            params.push(("tags", tags.clone()));
+        }
+        if self.all_tags {
+            params.push(("tag_match", "all".to_string()));
+        }
+        if let Some(source) = &self.source {
+            params.push(("source", source.clone()));
+        }
+        if let Some(after) = &self.after {
+            params.push(("created_after", after.clone()));
+        }
+        if let Some(before) = &self.before {
+            params.push(("created_before", before.clone()));
+        }
+        if let Some(min_score) = self.min_score {
+            params.push(("min_score", min_score.to_string()));
+        }
+        params
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    // Logs go to stderr so they can't corrupt `memorai mcp`'s stdout
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
 
     let cli = Cli::parse();
-    let config = Config::from_env();
+    let config = Config::load(cli.config.as_deref(), cli.overrides.into_map())?;
+    let base = collection_url(&config, cli.collection.as_deref());
+    let client = api_client(&config)?;
 
     match cli.command {
         Commands::Serve => serve(config).await,
-        Commands::Add { text, tags, source } => {
-            let tags: Vec<String> = tags
-                .map(|t| t.split(',').map(|s| s.trim().to_string()).collect())
-                .unwrap_or_default();
-            add_memory(config, text, tags, source).await
+        Commands::Mcp => mcp(config, cli.collection).await,
+        Commands::Add {
+            text,
+            tags,
+            source,
+            dedup,
+            auto_tag,
+            no_auto_tag,
+        } => {
+            let tags = tags.map(|t| parse_tags(&t)).unwrap_or_default();
+            let dedup = dedup.map(|d| d.parse::<dedup::DedupPolicy>()).transpose()?;
+            let auto_tag = (auto_tag || no_auto_tag).then_some(auto_tag);
+            add_memory(&client, &base, text, tags, source, dedup, auto_tag).await
+        }
+        Commands::Show { id, embedding } => show_memory(&client, &base, id, embedding).await,
+        Commands::Edit {
+            id,
+            text,
+            tags,
+            source,
+            clear_source,
+        } => {
+            let tags = tags.map(|t| parse_tags(&t));
+            let source = if clear_source { Some(None) } else { source.map(Some) };
+            edit_memory(&client, &base, id, text, tags, source).await
+        }
+        Commands::History { id } => history(&client, &base, id).await,
+        Commands::Revert { id, revision } => revert(&client, &base, id, revision).await,
+        Commands::Delete { id } => delete_memory(&client, &base, id).await,
+        Commands::Trash { command } => trash(&client, &base, command).await,
+        Commands::Search { query, options } => search(&client, &base, query, options).await,
+        Commands::Stats => stats(&client, &base).await,
+        Commands::Profile {
+            command: Some(command),
+            ..
+        } => profile_history(&client, &base, command).await,
+        Commands::Profile {
+            strategy,
+            tags,
+            max_memories,
+            token_budget,
+            refresh,
+            command: None,
+        } => {
+            let strategy = strategy.map(|s| s.parse::<profile::Strategy>()).transpose()?;
+            let mut params = Vec::new();
+            if let Some(strategy) = strategy {
+                params.push(("strategy", strategy.to_string()));
+            }
+            if let Some(tags) = tags {
+                params.push(("tags", tags));
+            }
+            if let Some(max) = max_memories {
+                params.push(("max_memories", max.to_string()));
+            }
+            if let Some(budget) = token_budget {
+                params.push(("token_budget", budget.to_string()));
+            }
+            if refresh {
+                params.push(("refresh", "true".to_string()));
+            }
+            generate_profile(&client, &base, params).await
+        }
+        Commands::Ask {
+            question,
+            limit,
+            min_score,
+            tags,
+            source,
+        } => {
+            let body = serde_json::json!({
+                "question": question,
+                "limit": limit,
+                "min_score": min_score,
+                "tags": tags.map(|t| parse_tags(&t)).unwrap_or_default(),
+                "source": source,
+            });
+            ask(&client, &base, body).await
+        }
+        Commands::Reembed { all } => reembed(&client, config, all).await,
+        Commands::Ingest {
+            path,
+            tags,
+            chunk_size,
+            overlap,
+        } => {
+            let tags = tags.map(|t| parse_tags(&t)).unwrap_or_default();
+            ingest_files(&client, &base, path, tags, chunk_size, overlap).await
         }
-        Commands::Search { query, limit } => search(config, query, limit).await,
-        Commands::Stats => stats(config).await,
-        Commands::Profile => generate_profile(config).await,
+        Commands::Export { output, embeddings } => export(&client, &base, output, embeddings).await,
+        Commands::Import { file, overwrite } => import(&client, &base, file, overwrite).await,
+        Commands::Consolidate {
+            threshold,
+            max_cluster_size,
+            archive,
+        } => {
+            let body = serde_json::json!({
+                "collection": cli.collection.as_deref().unwrap_or(db::DEFAULT_COLLECTION),
+                "threshold": threshold,
+                "max_cluster_size": max_cluster_size,
+                "archive": archive,
+            });
+            consolidate(&client, config, body).await
+        }
+        Commands::Collections { command } => collections(&client, config, command).await,
+        Commands::Keys { command } => keys(&config, command),
+        Commands::Config { command } => match command {
+            ConfigCommands::Show => show_config(&config),
+        },
     }
 }
 
+async fn mcp(config: Config, collection: Option<String>) -> Result<()> {
+    use std::sync::Arc;
+    use embeddings::EmbeddingClient;
+
+    let collection = collection.unwrap_or_else(|| db::DEFAULT_COLLECTION.to_string());
+    let embeddings = EmbeddingClient::new(&config)?;
+    // RocksDB allows one process per data directory
+    let db = db::init_db(&config).await.context(
+        "Failed to open the database; if `memorai serve` is running, point the MCP client at its /v1/mcp endpoint instead",
+    )?;
+    if !db::collection_exists(&db, &collection).await? {
+        anyhow::bail!("Collection '{}' not found", collection);
+    }
+
+    let server = mcp::McpServer::new(
+        db,
+        config,
+        Arc::new(embeddings),
+        collection,
+        Some("mcp".to_string()),
+        auth::Access::default(),
+    );
+    mcp::serve_stdio(server).await
+}
+
 async fn serve(config: Config) -> Result<()> {
     use std::sync::Arc;
     use embeddings::EmbeddingClient;
 
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Some(load_tls_config(cert, key)?),
+        // Config validation rejects a certificate without a key and vice versa
+        _ => None,
+    };
+
+    let embeddings = EmbeddingClient::new(&config)?;
     let db = db::init_db(&config).await?;
+    let host = config.host.clone();
     let port = config.port;
-    let embeddings = EmbeddingClient::new(&config);
+    let socket = config.socket.clone();
+    let cors_origins = config.cors_origins.clone();
+    let url = api_url(&config);
+
+    let keys = auth::ApiKeys::new(&config.data_dir);
+    match keys.keys()?.len() {
+        0 => tracing::warn!(
+            "No API keys configured; anyone who can reach the server can read and modify memories. Create one with `memorai keys create`"
+        ),
+        n => tracing::info!("API key authentication enabled ({} keys)", n),
+    }
+
+    let loopback = host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback());
+    if !loopback && tls.is_none() {
+        tracing::warn!(
+            "Listening on {} without TLS; API keys and memories travel in plain text",
+            host
+        );
+    }
+
+    let stale = db::count_stale_embeddings(&db, embeddings.model(), None).await?;
+    if stale > 0 {
+        tracing::warn!(
+            "{} memories were embedded with a model other than {} and are skipped by vector search; run `memorai reembed`",
+            stale,
+            embeddings.model()
+        );
+    }
 
     let state = Arc::new(tokio::sync::RwLock::new(server::AppState {
         db,
         config,
-        embeddings,
+        embeddings: Arc::new(embeddings),
+        jobs: jobs::Jobs::default(),
+        keys,
     }));
 
     let shared_state = actix_web::web::Data::new(state);
@@ -93,45 +637,156 @@ async fn serve(config: Config) -> Result<()> {
   ║         memorai v{}          ║
   ║   Local AI Memory System             ║
   ╠══════════════════════════════════════╣
-  ║  API:  http://localhost:{}         ║
-  ║  Docs: http://localhost:{}/health  ║
+  ║  API:  {}
+  ║  Docs: {}/health
   ╚══════════════════════════════════════╝
 "#,
         env!("CARGO_PKG_VERSION"),
-        port,
-        port
+        url,
+        url
     );
 
-    tracing::info!("Listening on 0.0.0.0:{}", port);
-
-    actix_web::HttpServer::new(move || {
+    let server = actix_web::HttpServer::new(move || {
         actix_web::App::new()
             .app_data(shared_state.clone())
-            .wrap(actix_cors::Cors::permissive())
+            .wrap(server::cors_policy(&cors_origins))
             .service(server::api_scope())
             .service(server::health_route())
-    })
-    .bind(format!("0.0.0.0:{}", port))?
-    .run()
-    .await?;
+    });
+
+    let mut server = match tls {
+        Some(tls) => {
+            tracing::info!("Listening on {}:{} (TLS)", host, port);
+            server.bind_rustls_0_23((host.as_str(), port), tls)?
+        }
+        None => {
+            tracing::info!("Listening on {}:{}", host, port);
+            server.bind((host.as_str(), port))?
+        }
+    };
+
+    if let Some(path) = socket {
+        #[cfg(unix)]
+        {
+            remove_stale_socket(&path)?;
+            server = server
+                .bind_uds(&path)
+                .with_context(|| format!("Failed to listen on {}", path.display()))?;
+            tracing::info!("Listening on unix:{}", path.display());
+        }
+        #[cfg(not(unix))]
+        anyhow::bail!("MEMORAI_SOCKET is only supported on Unix");
+    }
+
+    server.run().await?;
 
     Ok(())
 }
 
+fn load_tls_config(cert: &std::path::Path, key: &std::path::Path) -> Result<rustls::ServerConfig> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read TLS certificates from {}", cert.display()))?;
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("Failed to read TLS private key from {}", key.display()))?;
+
+    rustls::ServerConfig::builder_with_provider(std::sync::Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .context("Failed to configure TLS")?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .context("Invalid TLS certificate or key")
+}
+
+/// Remove a socket left behind by a previous run, which would make the bind fail.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    Ok(())
+}
+
 fn api_url(config: &Config) -> String {
-    format!("http://localhost:{}", config.port)
+    if let Some(url) = &config.api_url {
+        return url.trim_end_matches('/').to_string();
+    }
+
+    let scheme = if config.tls_cert.is_some() { "https" } else { "http" };
+    let host = match config.host.as_str() {
+        "0.0.0.0" | "::" => "localhost".to_string(),
+        h if h.contains(':') => format!("[{}]", h),
+        h => h.to_string(),
+    };
+    format!("{}://{}:{}", scheme, host, config.port)
+}
+
+/// HTTP client for the API server, sending `MEMORAI_API_KEY` when it's set
+fn api_client(config: &Config) -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(key) = &config.api_key {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", key))
+            .context("MEMORAI_API_KEY contains invalid characters")?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+
+    Ok(reqwest::Client::builder().default_headers(headers).build()?)
+}
+
+/// API root for a collection's routes
+fn collection_url(config: &Config, collection: Option<&str>) -> String {
+    match collection {
+        Some(name) => format!(
+            "{}/v1/collections/{}",
+            api_url(config),
+            urlencoding::encode(name)
+        ),
+        None => format!("{}/v1", api_url(config)),
+    }
+}
+
+fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+fn print_memory(mem: &serde_json::Value) {
+    println!("   Text: {}", mem["text"].as_str().unwrap_or(""));
+    if let Some(tags) = mem["tags"].as_array() {
+        if !tags.is_empty() {
+            let tag_strs: Vec<&str> = tags.iter().filter_map(|t| t.as_str()).collect();
+            println!("   Tags: {}", tag_strs.join(", "));
+        }
+    }
+    if let Some(tags) = mem["auto_tags"].as_array() {
+        let tag_strs: Vec<&str> = tags.iter().filter_map(|t| t.as_str()).collect();
+        println!("   Suggested tags: {}", tag_strs.join(", "));
+    }
+    if let Some(src) = mem["source"].as_str() {
+        println!("   Source: {}", src);
+    }
 }
 
 async fn add_memory(
-    config: Config,
+    client: &reqwest::Client,
+    base: &str,
     text: String,
     tags: Vec<String>,
     source: Option<String>,
+    dedup: Option<dedup::DedupPolicy>,
+    auto_tag: Option<bool>,
 ) -> Result<()> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/v1/memories", api_url(&config));
+    let url = format!("{}/memories", base);
 
-    let mut body = serde_json::json!({ "text": text, "tags": tags });
+    let mut body = serde_json::json!({ "text": text, "tags": tags, "dedup": dedup, "auto_tag": auto_tag });
     if let Some(src) = &source {
         body["source"] = serde_json::json!(src);
     }
@@ -142,16 +797,44 @@ async fn add_memory(
     if resp.status().is_success() {
         let data: serde_json::Value = resp.json().await?;
         if let Some(mem) = data.get("data") {
-            println!("✅ Memory stored (id: {})", mem["id"].as_str().unwrap_or("?"));
-            println!("   Text: {}", mem["text"].as_str().unwrap_or(""));
-            if let Some(tags) = mem["tags"].as_array() {
-                if !tags.is_empty() {
-                    let tag_strs: Vec<&str> = tags.iter().filter_map(|t| t.as_str()).collect();
-                    println!("   Tags: {}", tag_strs.join(", "));
-                }
+            let id = mem["id"].as_str().unwrap_or("?");
+            match mem["outcome"].as_str() {
+                Some("merged") => println!(
+                    "🔁 Merged into existing memory {} (similarity {:.3})",
+                    id,
+                    mem["similarity"].as_f64().unwrap_or(1.0)
+                ),
+                _ => println!("✅ Memory stored (id: {})", id),
+            }
+            print_memory(mem);
+        }
+    } else {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+    }
+    Ok(())
+}
+
+async fn show_memory(client: &reqwest::Client, base: &str, id: String, include_embedding: bool) -> Result<()> {
+    let mut url = format!("{}/memories/{}", base, urlencoding::encode(&id));
+    if include_embedding {
+        url.push_str("?include_embedding=true");
+    }
+
+    let resp = client.get(&url).send().await?;
+
+    if resp.status().is_success() {
+        let data: serde_json::Value = resp.json().await?;
+        if let Some(mem) = data.get("data") {
+            println!("🧠 Memory {}", mem["id"].as_str().unwrap_or("?"));
+            print_memory(mem);
+            println!("   Created: {}", mem["created_at"].as_str().unwrap_or("?"));
+            println!("   Updated: {}", mem["updated_at"].as_str().unwrap_or("?"));
+            if let Some(deleted) = mem["deleted_at"].as_str() {
+                println!("   In trash since: {}", deleted);
             }
-            if let Some(src) = mem["source"].as_str() {
-                println!("   Source: {}", src);
+            if let Some(embedding) = mem["embedding"].as_array() {
+                println!("   Embedding ({} dims): {}", embedding.len(), serde_json::to_string(embedding)?);
             }
         }
     } else {
@@ -161,13 +844,178 @@ async fn add_memory(
     Ok(())
 }
 
-async fn search(config: Config, query: String, limit: usize) -> Result<()> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/v1/search?q={}&limit={}", api_url(&config), urlencoding::encode(&query), limit);
+async fn edit_memory(
+    client: &reqwest::Client,
+    base: &str,
+    id: String,
+    text: Option<String>,
+    tags: Option<Vec<String>>,
+    source: Option<Option<String>>,
+) -> Result<()> {
+    let url = format!("{}/memories/{}", base, urlencoding::encode(&id));
+
+    let mut body = serde_json::json!({});
+    if let Some(text) = &text {
+        body["text"] = serde_json::json!(text);
+    }
+    if let Some(tags) = &tags {
+        body["tags"] = serde_json::json!(tags);
+    }
+    if let Some(src) = &source {
+        // `null` clears the source
+        body["source"] = serde_json::json!(src);
+    }
+
+    let resp = client.patch(&url).json(&body).send().await?;
+
+    if resp.status().is_success() {
+        let data: serde_json::Value = resp.json().await?;
+        if let Some(mem) = data.get("data") {
+            println!("✅ Memory updated (id: {})", mem["id"].as_str().unwrap_or("?"));
+            print_memory(mem);
+        }
+    } else {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+    }
+    Ok(())
+}
+
+async fn history(client: &reqwest::Client, base: &str, id: String) -> Result<()> {
+    let url = format!("{}/memories/{}/history", base, urlencoding::encode(&id));
 
-    println!("Searching for: \"{}\"", query);
     let resp = client.get(&url).send().await?;
 
+    if resp.status().is_success() {
+        let data: serde_json::Value = resp.json().await?;
+        for revision in data["data"].as_array().into_iter().flatten() {
+            println!(
+                "📜 {} {} at {}{}",
+                revision["id"].as_str().unwrap_or("?"),
+                revision["action"].as_str().unwrap_or("?"),
+                revision["created_at"].as_str().unwrap_or("?"),
+                revision["actor"]
+                    .as_str()
+                    .map(|a| format!(" by {}", a))
+                    .unwrap_or_default()
+            );
+            print_memory(revision);
+            println!();
+        }
+    } else {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+    }
+    Ok(())
+}
+
+async fn revert(client: &reqwest::Client, base: &str, id: String, revision: String) -> Result<()> {
+    let url = format!("{}/memories/{}/revert", base, urlencoding::encode(&id));
+
+    let resp = client
+        .post(&url)
+        .json(&serde_json::json!({ "revision": revision }))
+        .send()
+        .await?;
+
+    if resp.status().is_success() {
+        let data: serde_json::Value = resp.json().await?;
+        if let Some(mem) = data.get("data") {
+            println!("⏪ Memory {} reverted to revision {}", mem["id"].as_str().unwrap_or("?"), revision);
+            print_memory(mem);
+        }
+    } else {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+    }
+    Ok(())
+}
+
+async fn delete_memory(client: &reqwest::Client, base: &str, id: String) -> Result<()> {
+    let url = format!("{}/memories/{}", base, urlencoding::encode(&id));
+
+    let resp = client.delete(&url).send().await?;
+
+    if resp.status().is_success() {
+        println!("🗑️  Memory {} moved to trash (undo with `memorai trash restore {}`)", id, id);
+    } else {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+    }
+    Ok(())
+}
+
+async fn trash(client: &reqwest::Client, base: &str, command: TrashCommands) -> Result<()> {
+    let url = format!("{}/trash", base);
+
+    let resp = match &command {
+        TrashCommands::List { page, per_page } => {
+            client
+                .get(&url)
+                .query(&[("page", page), ("per_page", per_page)])
+                .send()
+                .await?
+        }
+        TrashCommands::Restore { id } => {
+            client
+                .post(format!("{}/memories/{}/restore", base, urlencoding::encode(id)))
+                .send()
+                .await?
+        }
+        TrashCommands::Purge { older_than_days } => {
+            let mut request = client.delete(&url);
+            if let Some(days) = older_than_days {
+                request = request.query(&[("older_than_days", days)]);
+            }
+            request.send().await?
+        }
+    };
+
+    let success = resp.status().is_success();
+    let data: serde_json::Value = resp.json().await?;
+    if !success {
+        println!("❌ {}", data["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    match command {
+        TrashCommands::List { .. } => {
+            let memories = data["data"].as_array().cloned().unwrap_or_default();
+            if memories.is_empty() {
+                println!("The trash is empty.");
+            }
+            for mem in &memories {
+                println!(
+                    "🗑️  {} (deleted {})",
+                    mem["id"].as_str().unwrap_or("?"),
+                    mem["deleted_at"].as_str().unwrap_or("?")
+                );
+                print_memory(mem);
+                println!();
+            }
+        }
+        TrashCommands::Restore { .. } => {
+            let mem = &data["data"];
+            println!("✅ Memory restored (id: {})", mem["id"].as_str().unwrap_or("?"));
+            print_memory(mem);
+        }
+        TrashCommands::Purge { .. } => {
+            let purged = &data["data"];
+            println!(
+                "✅ Purged {} memories deleted more than {} days ago",
+                purged["purged"], purged["older_than_days"]
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn search(client: &reqwest::Client, base: &str, query: String, options: SearchOptions) -> Result<()> {
+    let url = format!("{}/search", base);
+
+    println!("Searching for: \"{}\"", query);
+    let resp = client.get(&url).query(&options.to_params(&query)).send().await?;
+
     if resp.status().is_success() {
         let data: serde_json::Value = resp.json().await?;
         if let Some(results) = data["data"].as_array() {
@@ -196,17 +1044,30 @@ async fn search(config: Config, query: String, limit: usize) -> Result<()> {
     Ok(())
 }
 
-async fn stats(config: Config) -> Result<()> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/v1/stats", api_url(&config));
+async fn stats(client: &reqwest::Client, base: &str) -> Result<()> {
+    let url = format!("{}/stats", base);
 
     let resp = client.get(&url).send().await?;
 
     if resp.status().is_success() {
         let data: serde_json::Value = resp.json().await?;
         if let Some(stats) = data.get("data") {
-            println!("📊 memorai stats\n");
+            println!("📊 memorai stats ({})\n", stats["collection"].as_str().unwrap_or("?"));
             println!("Total memories: {}", stats["total_memories"]);
+            let archived = stats["archived_memories"].as_u64().unwrap_or(0);
+            if archived > 0 {
+                println!("Archived memories: {}", archived);
+            }
+            let trashed = stats["trashed_memories"].as_u64().unwrap_or(0);
+            if trashed > 0 {
+                println!("In trash: {}", trashed);
+            }
+            println!("Embedding model: {}", stats["embed_model"].as_str().unwrap_or("?"));
+
+            let stale = stats["stale_embeddings"].as_u64().unwrap_or(0);
+            if stale > 0 {
+                println!("⚠️  {} memories need re-embedding (run `memorai reembed`)", stale);
+            }
 
             if let Some(tags) = stats["top_tags"].as_array() {
                 if !tags.is_empty() {
@@ -227,26 +1088,621 @@ async fn stats(config: Config) -> Result<()> {
             }
         }
     } else {
-        println!("❌ Failed to get stats");
+        let err: serde_json::Value = resp.json().await.unwrap_or_default();
+        println!("❌ {}", err["error"].as_str().unwrap_or("Failed to get stats"));
     }
     Ok(())
 }
 
-async fn generate_profile(config: Config) -> Result<()> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/v1/profile", api_url(&config));
+async fn generate_profile(
+    client: &reqwest::Client,
+    base: &str,
+    mut params: Vec<(&str, String)>,
+) -> Result<()> {
+    let url = format!("{}/profile", base);
+    params.push(("stream", "true".to_string()));
 
     println!("Generating profile from stored memories...\n");
-    let resp = client.get(&url).send().await?;
+    let resp = client.get(&url).query(&params).send().await?;
 
     if resp.status().is_success() {
-        let data: serde_json::Value = resp.json().await?;
-        if let Some(profile) = data.get("data") {
-            println!("👤 Profile (based on {} memories):\n", profile["memory_count"]);
-            println!("{}", profile["profile"].as_str().unwrap_or(""));
+        let mut writing = false;
+        read_events(resp, |event, data| match event {
+            "progress" => println!("⏳ {}", data["message"].as_str().unwrap_or("")),
+            "token" => {
+                if !writing {
+                    println!("👤 Profile:\n");
+                    writing = true;
+                }
+                print_token(data["text"].as_str().unwrap_or(""));
+            }
+            "done" => {
+                // Stored profiles, and those of empty collections, don't
+                // involve the model
+                if !writing {
+                    print!("👤 Profile:\n\n{}", data["profile"].as_str().unwrap_or(""));
+                }
+                println!(
+                    "\n\n(based on {} memories, {})",
+                    data["memory_count"],
+                    data["strategy"].as_str().unwrap_or("?")
+                );
+                if data["cached"].as_bool().unwrap_or(false) {
+                    println!(
+                        "Stored profile from {}; {} memories added or edited since (--refresh to rewrite it)",
+                        data["generated_at"].as_str().unwrap_or("?"),
+                        data["pending_memories"]
+                    );
+                } else if data["incremental"].as_bool().unwrap_or(false) {
+                    println!("Updated the stored profile with new memories");
+                }
+            }
+            "error" => println!("\n❌ {}", data["error"].as_str().unwrap_or("Failed to generate profile")),
+            _ => {}
+        })
+        .await?;
+    } else {
+        let err: serde_json::Value = resp.json().await.unwrap_or_default();
+        println!("❌ {}", err["error"].as_str().unwrap_or("Failed to generate profile"));
+    }
+    Ok(())
+}
+
+async fn profile_history(client: &reqwest::Client, base: &str, command: ProfileCommands) -> Result<()> {
+    let resp = match &command {
+        ProfileCommands::History { page, per_page } => {
+            client
+                .get(format!("{}/profile/history", base))
+                .query(&[("page", page), ("per_page", per_page)])
+                .send()
+                .await?
+        }
+        ProfileCommands::Show { id, against } => {
+            let mut request = client.get(format!("{}/profile/history/{}", base, urlencoding::encode(id)));
+            if let Some(against) = against {
+                request = request.query(&[("against", against)]);
+            }
+            request.send().await?
+        }
+    };
+
+    let success = resp.status().is_success();
+    let data: serde_json::Value = resp.json().await?;
+    if !success {
+        println!("❌ {}", data["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    match command {
+        ProfileCommands::History { .. } => {
+            let snapshots = data["data"].as_array().cloned().unwrap_or_default();
+            if snapshots.is_empty() {
+                println!("No profiles stored yet.");
+            }
+            for snapshot in &snapshots {
+                let tags: Vec<&str> = snapshot["tags"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|t| t.as_str())
+                    .collect();
+                println!(
+                    "👤 {} at {} ({}, {} memories, {}{}, {})",
+                    snapshot["id"].as_str().unwrap_or("?"),
+                    snapshot["created_at"].as_str().unwrap_or("?"),
+                    if snapshot["incremental"].as_bool().unwrap_or(false) { "updated" } else { "written" },
+                    snapshot["memory_count"],
+                    snapshot["strategy"].as_str().unwrap_or("?"),
+                    if tags.is_empty() { String::new() } else { format!(" [{}]", tags.join(", ")) },
+                    snapshot["model"].as_str().unwrap_or("?")
+                );
+            }
+        }
+        ProfileCommands::Show { .. } => {
+            let snapshot = &data["data"];
+            println!(
+                "👤 Profile {} from {} ({} memories, {})",
+                snapshot["id"].as_str().unwrap_or("?"),
+                snapshot["created_at"].as_str().unwrap_or("?"),
+                snapshot["memory_count"],
+                snapshot["model"].as_str().unwrap_or("?")
+            );
+            match snapshot["compared_to"].as_str() {
+                Some(base) => println!(
+                    "Compared with {}: {} memories added, {} removed\n",
+                    base,
+                    snapshot["memories_added"].as_array().map_or(0, Vec::len),
+                    snapshot["memories_removed"].as_array().map_or(0, Vec::len)
+                ),
+                None => println!("The first profile\n"),
+            }
+            for line in snapshot["diff"].as_array().into_iter().flatten() {
+                let marker = match line["op"].as_str() {
+                    Some("added") => "+ ",
+                    Some("removed") => "- ",
+                    _ => "  ",
+                };
+                println!("{}{}", marker, line["text"].as_str().unwrap_or(""));
+            }
         }
+    }
+    Ok(())
+}
+
+async fn ask(client: &reqwest::Client, base: &str, mut body: serde_json::Value) -> Result<()> {
+    let url = format!("{}/ask", base);
+    body["stream"] = serde_json::Value::Bool(true);
+
+    let resp = client.post(&url).json(&body).send().await?;
+
+    if resp.status().is_success() {
+        let mut writing = false;
+        read_events(resp, |event, data| match event {
+            "token" => {
+                if !writing {
+                    print!("💡 ");
+                    writing = true;
+                }
+                print_token(data["text"].as_str().unwrap_or(""));
+            }
+            "done" if data["refused"].as_bool().unwrap_or(false) => {
+                println!("🤷 {}", data["answer"].as_str().unwrap_or(""));
+            }
+            "done" => {
+                println!();
+                if let Some(citations) = data["citations"].as_array().filter(|c| !c.is_empty()) {
+                    println!("\nSources:");
+                    for c in citations {
+                        println!(
+                            "  {} [score: {:.4}] {}",
+                            c["memory"]["id"].as_str().unwrap_or("?"),
+                            c["score"].as_f64().unwrap_or(0.0),
+                            c["memory"]["text"].as_str().unwrap_or("")
+                        );
+                    }
+                }
+            }
+            "error" => println!("\n❌ {}", data["error"].as_str().unwrap_or("Failed to answer")),
+            _ => {}
+        })
+        .await?;
     } else {
-        println!("❌ Failed to generate profile");
+        let err: serde_json::Value = resp.json().await.unwrap_or_default();
+        println!("❌ {}", err["error"].as_str().unwrap_or("Failed to answer"));
+    }
+    Ok(())
+}
+
+/// Read a Server-Sent Events response to the end, passing each event's
+/// name and JSON data to `on_event`.
+async fn read_events(
+    resp: reqwest::Response,
+    mut on_event: impl FnMut(&str, serde_json::Value),
+) -> Result<()> {
+    use futures::StreamExt;
+
+    let mut body = resp.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = body.next().await {
+        buffer.extend_from_slice(&chunk.context("Lost the connection to the server")?);
+
+        while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+            let block: Vec<u8> = buffer.drain(..end + 2).collect();
+            let block = String::from_utf8_lossy(&block);
+
+            let mut name = "message";
+            let mut data = String::new();
+            for line in block.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    name = value.trim();
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data.push_str(value.trim_start());
+                }
+            }
+            on_event(name, serde_json::from_str(&data).unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+fn print_token(text: &str) {
+    use std::io::Write;
+
+    print!("{}", text);
+    let _ = std::io::stdout().flush();
+}
+
+async fn reembed(client: &reqwest::Client, config: Config, all: bool) -> Result<()> {
+    let url = format!("{}/v1/admin/reembed", api_url(&config));
+
+    let resp = client
+        .post(&url)
+        .json(&serde_json::json!({ "all": all }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    let data: serde_json::Value = resp.json().await?;
+    let job_id = data["data"]["id"].as_str().unwrap_or_default().to_string();
+    println!("Re-embedding memories (job {})...", job_id);
+
+    follow_job(client, &config, &job_id, "Re-embedding").await
+}
+
+async fn consolidate(client: &reqwest::Client, config: Config, body: serde_json::Value) -> Result<()> {
+    let url = format!("{}/v1/admin/consolidate", api_url(&config));
+
+    let resp = client.post(&url).json(&body).send().await?;
+
+    if !resp.status().is_success() {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    let data: serde_json::Value = resp.json().await?;
+    let job_id = data["data"]["id"].as_str().unwrap_or_default().to_string();
+    println!("Consolidating memories (job {})...", job_id);
+
+    follow_job(client, &config, &job_id, "Consolidation").await
+}
+
+/// Poll a background job, printing its progress until it finishes.
+async fn follow_job(client: &reqwest::Client, config: &Config, job_id: &str, label: &str) -> Result<()> {
+    let url = format!("{}/v1/jobs/{}", api_url(config), job_id);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let data: serde_json::Value = client.get(&url).send().await?.json().await?;
+        let job = &data["data"];
+        print!(
+            "\r  {}/{} processed, {} failed",
+            job["processed"], job["total"], job["failed"]
+        );
+        std::io::Write::flush(&mut std::io::stdout())?;
+
+        match job["state"].as_str() {
+            Some("running") => continue,
+            Some("completed") => {
+                println!("\n✅ {} complete", label);
+                if let Some(errors) = job["errors"].as_array() {
+                    for err in errors {
+                        println!("   {}", err.as_str().unwrap_or_default());
+                    }
+                }
+                break;
+            }
+            _ => {
+                println!(
+                    "\n❌ {} failed: {}",
+                    label,
+                    job["error"].as_str().unwrap_or("Unknown error")
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extensions read by `memorai ingest`, and whether they are Markdown
+const INGEST_EXTENSIONS: [(&str, bool); 3] = [("md", true), ("markdown", true), ("txt", false)];
+
+async fn ingest_files(
+    client: &reqwest::Client,
+    base: &str,
+    path: std::path::PathBuf,
+    tags: Vec<String>,
+    chunk_size: usize,
+    overlap: usize,
+) -> Result<()> {
+    let url = format!("{}/documents", base);
+    let root = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut files = Vec::new();
+    collect_files(&root, &mut files)?;
+    files.sort();
+    println!("Ingesting {} files from {}...", files.len(), root.display());
+
+    let (mut created, mut updated, mut unchanged, mut failed, mut chunks) = (0, 0, 0, 0, 0);
+    for file in files {
+        let markdown = file
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| INGEST_EXTENSIONS.iter().find(|(ext, _)| e.eq_ignore_ascii_case(ext)))
+            .is_some_and(|(_, markdown)| *markdown);
+        let relative = file.strip_prefix(&root).unwrap_or(&file).display().to_string();
+        let relative = if relative.is_empty() { file.display().to_string() } else { relative };
+
+        let text = match std::fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(err) => {
+                failed += 1;
+                println!("❌ {}: {}", relative, err);
+                continue;
+            }
+        };
+
+        let resp = client
+            .post(&url)
+            .json(&serde_json::json!({
+                "source": file.display().to_string(),
+                "text": text,
+                "tags": tags,
+                "markdown": markdown,
+                "chunk_size": chunk_size,
+                "chunk_overlap": overlap,
+            }))
+            .send()
+            .await?;
+
+        let status = resp.status();
+        let body = resp.text().await?;
+        let data: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+        if !status.is_success() {
+            failed += 1;
+            let err = data["error"].as_str().map(str::to_string).unwrap_or(body);
+            println!("❌ {}: {}", relative, err.trim());
+            continue;
+        }
+
+        let count = data["data"]["chunks"].as_u64().unwrap_or(0);
+        match data["data"]["status"].as_str() {
+            Some("created") => {
+                created += 1;
+                chunks += count;
+                println!("  + {} ({} chunks)", relative, count);
+            }
+            Some("updated") => {
+                updated += 1;
+                chunks += count;
+                println!("  ~ {} ({} chunks)", relative, count);
+            }
+            _ => unchanged += 1,
+        }
+    }
+
+    println!(
+        "✅ {} new, {} updated, {} unchanged, {} failed ({} chunks stored)",
+        created, updated, unchanged, failed, chunks
+    );
+    Ok(())
+}
+
+/// Files under `path` with an ingestable extension, skipping hidden entries
+/// such as `.obsidian`, `.git` and `.trash`.
+fn collect_files(path: &std::path::Path, files: &mut Vec<std::path::PathBuf>) -> Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let entries = std::fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let entry_path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry_path, files)?;
+        } else if entry_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| INGEST_EXTENSIONS.iter().any(|(ext, _)| e.eq_ignore_ascii_case(ext)))
+        {
+            files.push(entry_path);
+        }
+    }
+    Ok(())
+}
+
+async fn export(
+    client: &reqwest::Client,
+    base: &str,
+    output: Option<std::path::PathBuf>,
+    include_embeddings: bool,
+) -> Result<()> {
+    use std::io::Write;
+
+    let url = format!("{}/memories/export", base);
+    let mut resp = client
+        .get(&url)
+        .query(&[("include_embeddings", include_embeddings)])
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let err: serde_json::Value = resp.json().await?;
+        println!("❌ {}", err["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    let mut out: Box<dyn Write> = match &output {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    let mut count = 0;
+    while let Some(chunk) = resp.chunk().await.context("Export interrupted")? {
+        count += chunk.iter().filter(|b| **b == b'\n').count();
+        out.write_all(&chunk)?;
+    }
+    out.flush()?;
+
+    // Keep stdout clean for the export itself
+    if let Some(path) = output {
+        eprintln!("✅ Exported {} memories to {}", count, path.display());
+    }
+    Ok(())
+}
+
+async fn import(client: &reqwest::Client, base: &str, file: std::path::PathBuf, overwrite: bool) -> Result<()> {
+    let url = format!("{}/memories/import", base);
+    let reader = tokio::fs::File::open(&file)
+        .await
+        .with_context(|| format!("Failed to open {}", file.display()))?;
+    let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader));
+
+    println!("Importing {}...", file.display());
+    let resp = client
+        .post(&url)
+        .query(&[("overwrite", overwrite)])
+        .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+        .body(body)
+        .send()
+        .await?;
+
+    let ok = resp.status().is_success();
+    let data: serde_json::Value = resp.json().await?;
+    if !ok {
+        println!("❌ {}", data["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    let result = &data["data"];
+    println!(
+        "✅ Imported {} memories ({} re-embedded), skipped {} existing, {} failed",
+        result["imported"], result["reembedded"], result["skipped"], result["failed"]
+    );
+    if let Some(errors) = result["errors"].as_array() {
+        for err in errors {
+            println!("   {}", err.as_str().unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+async fn collections(client: &reqwest::Client, config: Config, command: CollectionCommands) -> Result<()> {
+    let url = format!("{}/v1/collections", api_url(&config));
+
+    let resp = match &command {
+        CollectionCommands::List => client.get(&url).send().await?,
+        CollectionCommands::Create { name, description } => {
+            client
+                .post(&url)
+                .json(&serde_json::json!({ "name": name, "description": description }))
+                .send()
+                .await?
+        }
+        CollectionCommands::Delete { name } => {
+            client
+                .delete(format!("{}/{}", url, urlencoding::encode(name)))
+                .send()
+                .await?
+        }
+    };
+
+    let success = resp.status().is_success();
+    let data: serde_json::Value = resp.json().await?;
+    if !success {
+        println!("❌ {}", data["error"].as_str().unwrap_or("Unknown error"));
+        return Ok(());
+    }
+
+    match command {
+        CollectionCommands::List => {
+            for c in data["data"].as_array().into_iter().flatten() {
+                print!("{} ({} memories)", c["name"].as_str().unwrap_or("?"), c["memory_count"]);
+                if let Some(description) = c["description"].as_str() {
+                    print!(" — {}", description);
+                }
+                println!();
+            }
+        }
+        CollectionCommands::Create { name, .. } => println!("✅ Collection '{}' created", name),
+        CollectionCommands::Delete { .. } => {
+            println!("✅ {}", data["data"].as_str().unwrap_or("Collection deleted"))
+        }
+    }
+    Ok(())
+}
+
+fn keys(config: &Config, command: KeyCommands) -> Result<()> {
+    let keys = auth::ApiKeys::new(&config.data_dir);
+
+    match command {
+        KeyCommands::Create {
+            name,
+            scopes,
+            collections,
+        } => {
+            let scopes = scopes
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<auth::KeyScope>>>()?;
+            let collections = collections.map(|c| parse_tags(&c));
+            if let Some(invalid) = collections
+                .iter()
+                .flatten()
+                .find(|c| !db::is_valid_collection_name(c))
+            {
+                anyhow::bail!("Invalid collection name '{}'", invalid);
+            }
+
+            let (key, secret) = keys.create(&name, scopes, collections)?;
+            println!("✅ API key '{}' created (id: {})\n", key.name, key.id);
+            println!("   {}\n", secret);
+            println!("Store it now, it can't be shown again. Send it as `Authorization: Bearer <key>`, or set MEMORAI_API_KEY for the CLI.");
+        }
+        KeyCommands::List => {
+            let list = keys.keys()?;
+            if list.is_empty() {
+                println!("No API keys; the API is open to anyone who can reach it.");
+            }
+            for key in list {
+                let scopes: Vec<String> = key.scopes.iter().map(|s| s.to_string()).collect();
+                let collections = key
+                    .collections
+                    .map(|c| c.join(","))
+                    .unwrap_or_else(|| "all".to_string());
+                println!(
+                    "{}  {:<20} {}…  scopes: {}  collections: {}  created: {}",
+                    key.id,
+                    key.name,
+                    key.prefix,
+                    scopes.join(","),
+                    collections,
+                    key.created_at
+                );
+            }
+        }
+        KeyCommands::Revoke { id } => match keys.revoke(&id)? {
+            Some(key) => println!("✅ API key '{}' ({}) revoked", key.name, key.id),
+            None => println!("❌ No API key with id {}", id),
+        },
+    }
+    Ok(())
+}
+
+fn show_config(config: &Config) -> Result<()> {
+    match &config.config_file {
+        Some(path) => println!("Config file: {}\n", path.display()),
+        None => println!("Config file: none\n"),
+    }
+
+    let width = config.settings.iter().map(|s| s.key.len()).max().unwrap_or(0);
+    for setting in &config.settings {
+        println!(
+            "{:<width$} = {:<40} ({})",
+            setting.key,
+            setting.value,
+            setting.source,
+            width = width
+        );
     }
     Ok(())
 }
@@ -0,0 +1,349 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::auth::{Access, KeyScope};
+use crate::autotag::Tagger;
+use crate::config::Config;
+use crate::db::{self, Db, MemoryFilter, MemoryState};
+use crate::dedup::{self, Written};
+use crate::embeddings::EmbeddingClient;
+use crate::models::{
+    CreateMemoryRequest, CreateMemoryResponse, ListQuery, MemoryResponse, ProfileQuery, SearchQuery,
+    WriteOutcome,
+};
+use crate::profile;
+use crate::search;
+
+/// Protocol revisions this server speaks, newest first
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A Model Context Protocol server exposing one collection's memories as
+/// tools. Each tool goes through the same database and embedding code as
+/// the matching REST endpoint.
+pub struct McpServer {
+    db: Db,
+    config: Config,
+    embeddings: Arc<EmbeddingClient>,
+    collection: String,
+    /// Recorded in the history of memories the tools change
+    actor: Option<String>,
+    /// What the caller's key may do; tools that change memories need `write`
+    access: Access,
+}
+
+#[derive(Deserialize)]
+struct ForgetArgs {
+    id: String,
+}
+
+impl McpServer {
+    pub fn new(
+        db: Db,
+        config: Config,
+        embeddings: Arc<EmbeddingClient>,
+        collection: String,
+        actor: Option<String>,
+        access: Access,
+    ) -> Self {
+        Self {
+            db,
+            config,
+            embeddings,
+            collection,
+            actor,
+            access,
+        }
+    }
+
+    /// Handle a JSON-RPC message or batch of messages. Returns `None` when
+    /// there is nothing to send back, which is the case for notifications.
+    pub async fn handle_payload(&self, payload: &[u8]) -> Option<Value> {
+        match serde_json::from_slice::<Value>(payload) {
+            Ok(Value::Array(batch)) if !batch.is_empty() => {
+                let mut responses = Vec::new();
+                for message in batch {
+                    responses.extend(self.handle(message).await);
+                }
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            Ok(message) => self.handle(message).await,
+            Err(err) => Some(error(Value::Null, PARSE_ERROR, format!("Parse error: {}", err))),
+        }
+    }
+
+    async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // A response to a request we never send, or garbage
+            return id.map(|id| error(id, INVALID_REQUEST, "Invalid request"));
+        };
+        // Notifications (`notifications/initialized`, `notifications/cancelled`)
+        // need no reply and change nothing here
+        let id = id?;
+
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => Ok(initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools() })),
+            "tools/call" => self.call_tool(params).await,
+            other => Err((METHOD_NOT_FOUND, format!("Method not found: {}", other))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error(id, code, message),
+        })
+    }
+
+    /// Run a tool. Failures inside the tool are reported in the result, as
+    /// the protocol asks, so the model can see them and try again.
+    async fn call_tool(&self, params: Value) -> Result<Value, (i64, String)> {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or_default();
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let writes = matches!(name, "remember" | "forget");
+        let result = match name {
+            _ if writes && !self.access.allows(KeyScope::Write) => {
+                Err(anyhow::anyhow!("The {} tool needs an API key with the write scope", name))
+            }
+            "remember" => self.remember(args).await,
+            "recall" => self.recall(args).await,
+            "forget" => self.forget(args).await,
+            "list" => self.list(args).await,
+            "profile" => self.profile(args).await,
+            other => return Err((INVALID_PARAMS, format!("Unknown tool: {}", other))),
+        };
+
+        let (text, is_error) = match result {
+            Ok(value) => (serde_json::to_string_pretty(&value).unwrap_or_default(), false),
+            Err(err) => (format!("{:#}", err), true),
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    async fn remember(&self, args: Value) -> Result<Value> {
+        let mut req: CreateMemoryRequest = parse_args(args)?;
+        if req.text.trim().is_empty() {
+            anyhow::bail!("Text cannot be empty");
+        }
+        Tagger::new(&self.db, &self.config, &self.collection).tag(&mut req).await;
+
+        let embedding = self.embeddings.embed(&req.text).await.context("Embedding failed")?;
+        let written = dedup::create_memory(
+            &self.db,
+            &self.collection,
+            req,
+            embedding,
+            self.embeddings.model(),
+            self.config.dedup(),
+            self.actor.as_deref(),
+        )
+        .await
+        .context("Failed to create memory")?;
+
+        let (memory, outcome, similarity) = match written {
+            Written::Created(memory) => (memory, WriteOutcome::Created, None),
+            Written::Merged(memory, similarity) => (memory, WriteOutcome::Merged, Some(similarity)),
+            Written::Rejected(existing, similarity) => (existing, WriteOutcome::Rejected, Some(similarity)),
+        };
+        Ok(serde_json::to_value(CreateMemoryResponse {
+            memory: MemoryResponse::from_memory(memory),
+            outcome,
+            similarity,
+        })?)
+    }
+
+    async fn recall(&self, args: Value) -> Result<Value> {
+        let query: SearchQuery = parse_args(args)?;
+        if query.q.trim().is_empty() {
+            anyhow::bail!("Query cannot be empty");
+        }
+
+        let filter = MemoryFilter {
+            collection: Some(self.collection.clone()),
+            ..search::filter_from_query(&query)?
+        };
+        let results = search::search(&self.db, &self.embeddings, &query, &filter)
+            .await
+            .context("Search failed")?;
+        Ok(serde_json::to_value(results)?)
+    }
+
+    async fn forget(&self, args: Value) -> Result<Value> {
+        let ForgetArgs { id } = parse_args(args)?;
+        match db::delete_memory(&self.db, &self.collection, &id, self.actor.as_deref()).await? {
+            Some(memory) => Ok(serde_json::to_value(MemoryResponse::from_memory(memory))?),
+            None => anyhow::bail!("Memory not found"),
+        }
+    }
+
+    async fn list(&self, args: Value) -> Result<Value> {
+        let query: ListQuery = parse_args(args)?;
+        let filter = MemoryFilter {
+            collection: Some(self.collection.clone()),
+            tags: query.tag.iter().cloned().collect(),
+            source: query.source.clone(),
+            state: if query.archived {
+                MemoryState::Archived
+            } else {
+                MemoryState::Active
+            },
+            ..Default::default()
+        };
+
+        let memories = db::get_memories_paginated(
+            &self.db,
+            query.page.unwrap_or(1),
+            query.per_page.unwrap_or(20).min(100),
+            &filter,
+        )
+        .await
+        .context("Failed to list memories")?;
+        let memories: Vec<MemoryResponse> = memories.into_iter().map(MemoryResponse::from_memory).collect();
+        Ok(serde_json::to_value(memories)?)
+    }
+
+    async fn profile(&self, args: Value) -> Result<Value> {
+        let query: ProfileQuery = parse_args(args)?;
+        let options = profile::ProfileOptions::from_query(&query, &self.config)?;
+        let profile = profile::get_profile(&self.db, &self.config, &self.collection, &options, None).await?;
+        Ok(serde_json::to_value(profile)?)
+    }
+}
+
+/// Serve MCP over stdin and stdout, one JSON-RPC message per line, until
+/// stdin closes.
+pub async fn serve_stdio(server: McpServer) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await.context("Failed to read stdin")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = server.handle_payload(line.as_bytes()).await {
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            stdout.write_all(&out).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+fn initialize(params: &Value) -> Value {
+    // Answer with the client's revision if we speak it, otherwise our newest
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let version = PROTOCOL_VERSIONS
+        .into_iter()
+        .find(|v| Some(*v) == requested)
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "memorai", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": "Long-term memory. Use recall before answering questions that may depend on \
+                         what the user told you before, and remember to store durable facts, \
+                         preferences and decisions.",
+    })
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "remember",
+            "description": "Store a memory. Near-duplicates of existing memories are handled by the server's dedup policy.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string", "description": "The text to remember" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "source": { "type": "string", "description": "Where the memory came from" },
+                    "auto_tag": { "type": "boolean", "description": "Have the server suggest tags, or not, regardless of its default" }
+                },
+                "required": ["text"]
+            }
+        },
+        {
+            "name": "recall",
+            "description": "Search memories by meaning, keywords or both, best match first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "What to look for" },
+                    "limit": { "type": "integer", "minimum": 1, "description": "Max results (default 5, at most 50)" },
+                    "mode": { "type": "string", "enum": ["vector", "keyword", "hybrid"] },
+                    "tags": { "type": "string", "description": "Comma-separated tags to filter on" },
+                    "source": { "type": "string" },
+                    "created_after": { "type": "string", "description": "YYYY-MM-DD or RFC 3339" },
+                    "created_before": { "type": "string", "description": "YYYY-MM-DD or RFC 3339" }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "forget",
+            "description": "Move a memory to the trash, where it can still be restored.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "Memory id" }
+                },
+                "required": ["id"]
+            }
+        },
+        {
+            "name": "list",
+            "description": "List memories, most recently created first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "page": { "type": "integer", "minimum": 1 },
+                    "per_page": { "type": "integer", "minimum": 1, "maximum": 100 },
+                    "tag": { "type": "string" },
+                    "source": { "type": "string" }
+                }
+            }
+        },
+        {
+            "name": "profile",
+            "description": "Summarise what the memories say about the user: interests, expertise and preferences. The stored profile is returned while it's up to date.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "strategy": { "type": "string", "enum": ["recent", "diverse", "tag"] },
+                    "tags": { "type": "string", "description": "Comma-separated tags to build the profile from" },
+                    "refresh": { "type": "boolean", "description": "Rewrite the profile instead of using the stored one" }
+                }
+            }
+        }
+    ])
+}
+
+fn parse_args<T: DeserializeOwned>(args: Value) -> Result<T> {
+    serde_json::from_value(args).context("Invalid arguments")
+}
+
+fn error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::Thing;
 
+use crate::dedup::DedupPolicy;
+use crate::profile::Strategy;
+
 // Database record
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Memory {
@@ -9,10 +12,65 @@ pub struct Memory {
     pub tags: Vec<String>,
     pub source: Option<String>,
     pub embedding: Vec<f32>,
+    /// Collection the memory belongs to; memories from before collections
+    /// existed live in the default one
+    #[serde(default = "default_collection")]
+    pub collection: String,
+    /// Model that produced `embedding`
+    #[serde(default)]
+    pub embed_model: Option<String>,
+    #[serde(default)]
+    pub embed_dim: Option<usize>,
+    /// Set when the memory was archived, e.g. after consolidation; archived
+    /// memories are left out of search, listings and stats
+    #[serde(default)]
+    pub archived_at: Option<String>,
+    /// Ids of the memories this one was consolidated from
+    #[serde(default)]
+    pub consolidated_from: Option<Vec<String>>,
+    /// Id of the memory this one was consolidated into
+    #[serde(default)]
+    pub consolidated_into: Option<String>,
+    /// Set while the memory is in the trash; trashed memories are left out of
+    /// everything but the trash listing until restored or purged
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    /// API key that last changed the memory
+    #[serde(default)]
+    pub updated_by: Option<String>,
+    /// Those of `tags` that the chat model suggested
+    #[serde(default)]
+    pub auto_tags: Option<Vec<String>>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+fn default_collection() -> String {
+    crate::db::DEFAULT_COLLECTION.to_string()
+}
+
+// Id and text only, for jobs that walk every memory
+#[derive(Debug, Deserialize)]
+pub struct MemoryText {
+    pub id: Thing,
+    pub text: String,
+}
+
+// Id, creation time and embedding only, for choosing memories by similarity
+#[derive(Debug, Deserialize)]
+pub struct MemoryVector {
+    pub id: Thing,
+    pub embedding: Vec<f32>,
+    pub created_at: String,
+}
+
+// Id and tags only, for choosing memories by tag
+#[derive(Debug, Deserialize)]
+pub struct MemoryTags {
+    pub id: Thing,
+    pub tags: Vec<String>,
+}
+
 // API request to create a memory
 #[derive(Debug, Deserialize)]
 pub struct CreateMemoryRequest {
@@ -20,23 +78,145 @@ pub struct CreateMemoryRequest {
     #[serde(default)]
     pub tags: Vec<String>,
     pub source: Option<String>,
+    /// Overrides the server's dedup policy for this memory
+    pub dedup: Option<DedupPolicy>,
+    /// Overrides the server's `auto_tag` setting for this memory
+    pub auto_tag: Option<bool>,
+    /// Tags the chat model added, filled in by the server
+    #[serde(skip)]
+    pub auto_tags: Vec<String>,
+}
+
+// API request to update a memory; omitted fields are left unchanged
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemoryRequest {
+    pub text: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// `Some(None)` when the request sets `"source": null`, clearing it
+    #[serde(default, deserialize_with = "double_option")]
+    pub source: Option<Option<String>>,
+}
+
+/// Tells a field that is `null` apart from one that is missing, which
+/// `#[serde(default)]` leaves as `None`
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 // API request for bulk import
 #[derive(Debug, Deserialize)]
 pub struct BulkCreateRequest {
     pub memories: Vec<CreateMemoryRequest>,
+    /// Dedup policy for items that don't set their own
+    pub dedup: Option<DedupPolicy>,
+    /// Auto-tagging for items that don't set their own
+    pub auto_tag: Option<bool>,
+}
+
+// API request to store a document as chunked memories
+#[derive(Debug, Deserialize)]
+pub struct IngestDocumentRequest {
+    /// Where the document came from, e.g. its file path. Ingesting the same
+    /// source again replaces its memories.
+    pub source: String,
+    pub text: String,
+    /// Tags for every chunk, on top of any in the front matter
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Parse front matter and split at headings; plain text is only split by size
+    #[serde(default = "default_true")]
+    pub markdown: bool,
+    /// Maximum characters per chunk
+    pub chunk_size: Option<usize>,
+    /// Characters repeated from the end of one chunk at the start of the next
+    pub chunk_overlap: Option<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentStatus {
+    Created,
+    Updated,
+    /// Same content and options as the last ingest; nothing was re-embedded
+    Unchanged,
+}
+
+// API response for an ingested document
+#[derive(Debug, Serialize)]
+pub struct IngestDocumentResponse {
+    pub source: String,
+    pub status: DocumentStatus,
+    pub chunks: usize,
+}
+
+/// What happened to a memory sent for creation
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteOutcome {
+    Created,
+    /// Folded into an existing duplicate
+    Merged,
+    /// Not stored because it duplicates an existing memory
+    Rejected,
+}
+
+// API response for a created memory; for a merged or rejected one, `memory`
+// is the existing duplicate
+#[derive(Debug, Serialize)]
+pub struct CreateMemoryResponse {
+    #[serde(flatten)]
+    pub memory: MemoryResponse,
+    pub outcome: WriteOutcome,
+    /// Similarity to the duplicate (1.0 for the same text)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+}
+
+// A bulk item that matched an existing memory
+#[derive(Debug, Serialize)]
+pub struct BulkDuplicate {
+    pub index: usize,
+    pub outcome: WriteOutcome,
+    pub duplicate_of: String,
+    pub similarity: f32,
 }
 
 // API response for a memory
 #[derive(Debug, Serialize)]
 pub struct MemoryResponse {
     pub id: String,
+    pub collection: String,
     pub text: String,
     pub tags: Vec<String>,
     pub source: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embed_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embed_dim: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consolidated_from: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consolidated_into: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_tags: Option<Vec<String>>,
 }
 
 impl MemoryResponse {
@@ -47,11 +227,33 @@ impl MemoryResponse {
             .unwrap_or_default();
         Self {
             id,
+            collection: m.collection,
             text: m.text,
             tags: m.tags,
             source: m.source,
             created_at: m.created_at,
             updated_at: m.updated_at,
+            embedding: None,
+            embed_model: None,
+            embed_dim: None,
+            archived_at: m.archived_at,
+            consolidated_from: m.consolidated_from,
+            consolidated_into: m.consolidated_into,
+            deleted_at: m.deleted_at,
+            updated_by: m.updated_by,
+            auto_tags: m.auto_tags.filter(|tags| !tags.is_empty()),
+        }
+    }
+
+    pub fn with_embedding(m: Memory) -> Self {
+        let embedding = m.embedding.clone();
+        let embed_model = m.embed_model.clone();
+        let embed_dim = m.embed_dim;
+        Self {
+            embedding: Some(embedding),
+            embed_model,
+            embed_dim,
+            ..Self::from_memory(m)
         }
     }
 }
@@ -63,11 +265,64 @@ pub struct SearchResult {
     pub score: f32,
 }
 
+// How search results are ranked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Cosine similarity between embeddings
+    #[default]
+    Vector,
+    /// BM25 full-text score on the memory text
+    Keyword,
+    /// Reciprocal rank fusion of the vector and keyword rankings
+    Hybrid,
+}
+
 // Search query params
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct SearchQuery {
+    #[serde(alias = "query")]
     pub q: String,
     pub limit: Option<usize>,
+    /// Score every memory exactly instead of using the HNSW index
+    #[serde(default)]
+    pub exact: bool,
+    #[serde(default)]
+    pub mode: SearchMode,
+    /// Weight of the vector ranking in hybrid mode (default 1.0)
+    pub vector_weight: Option<f32>,
+    /// Weight of the keyword ranking in hybrid mode (default 1.0)
+    pub keyword_weight: Option<f32>,
+    /// Comma-separated tags to filter on
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub tag_match: TagMatch,
+    pub source: Option<String>,
+    /// RFC 3339 timestamps or YYYY-MM-DD dates
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+    /// Drop results scoring below this: cosine similarity in vector mode,
+    /// BM25 in keyword mode, and in hybrid mode the similarity a vector
+    /// match needs to be fused (keyword matches are fused regardless)
+    pub min_score: Option<f32>,
+}
+
+// Whether a tag filter needs any or all of the given tags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+// Single memory query params
+#[derive(Debug, Deserialize)]
+pub struct GetMemoryQuery {
+    #[serde(default)]
+    pub include_embedding: bool,
 }
 
 // List query params
@@ -77,12 +332,46 @@ pub struct ListQuery {
     pub per_page: Option<usize>,
     pub tag: Option<String>,
     pub source: Option<String>,
+    /// List archived memories instead of active ones
+    #[serde(default)]
+    pub archived: bool,
+}
+
+// Trash listing query params
+#[derive(Debug, Deserialize)]
+pub struct TrashQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+// Purge query params
+#[derive(Debug, Deserialize)]
+pub struct PurgeQuery {
+    /// Only purge memories trashed at least this many days ago; defaults to
+    /// the configured retention, and 0 empties the trash
+    pub older_than_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurgeResponse {
+    pub purged: usize,
+    pub older_than_days: u32,
 }
 
 // Stats response
 #[derive(Debug, Serialize)]
 pub struct StatsResponse {
+    pub collection: String,
     pub total_memories: usize,
+    /// Archived memories, not included in the other counts
+    pub archived_memories: usize,
+    /// Memories in the trash, not included in the other counts
+    pub trashed_memories: usize,
+    /// Model used for new embeddings and queries
+    pub embed_model: String,
+    /// Active and archived memories embedded with a different model, skipped
+    /// by vector search
+    pub stale_embeddings: usize,
     pub tags: Vec<TagCount>,
     pub sources: Vec<SourceCount>,
 }
@@ -99,26 +388,366 @@ pub struct SourceCount {
     pub count: usize,
 }
 
+// A memory's content after one change, as recorded in its history
+#[derive(Debug, Deserialize)]
+pub struct Revision {
+    pub id: Thing,
+    /// Id of the memory that changed
+    pub memory: String,
+    /// `create`, `update`, `delete`, `restore`, `archive`, or `snapshot` for
+    /// memories that existed before history was kept
+    pub action: String,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    /// API key that made the change
+    pub actor: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevisionResponse {
+    pub id: String,
+    pub memory_id: String,
+    pub action: String,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub actor: Option<String>,
+    pub created_at: String,
+}
+
+impl RevisionResponse {
+    pub fn from_revision(r: Revision) -> Self {
+        Self {
+            id: r.id.id.to_string(),
+            memory_id: r.memory,
+            action: r.action,
+            text: r.text,
+            tags: r.tags,
+            source: r.source,
+            actor: r.actor,
+            created_at: r.created_at,
+        }
+    }
+}
+
+// API request to revert a memory to an earlier revision
+#[derive(Debug, Deserialize)]
+pub struct RevertRequest {
+    pub revision: String,
+}
+
+// Profile query params; omitted options come from the configuration
+#[derive(Debug, Deserialize)]
+pub struct ProfileQuery {
+    pub strategy: Option<Strategy>,
+    /// Comma-separated tags
+    pub tags: Option<String>,
+    pub max_memories: Option<usize>,
+    pub token_budget: Option<usize>,
+    /// Write the profile from scratch instead of serving or updating the
+    /// stored one
+    #[serde(default)]
+    pub refresh: bool,
+    /// Send Server-Sent Events as the profile is written
+    #[serde(default)]
+    pub stream: bool,
+}
+
 // Profile response
 #[derive(Debug, Serialize)]
 pub struct ProfileResponse {
     pub profile: String,
+    /// Memories the profile covers
     pub memory_count: usize,
+    pub strategy: Strategy,
+    /// Prompts the memories were split across before being combined
+    pub chunks: usize,
+    /// Stored snapshot id; absent when there were no memories to profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Chat model that wrote the profile
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>,
+    /// Served from the stored snapshot without asking the chat model
+    pub cached: bool,
+    /// Updated from the previous snapshot instead of written from scratch
+    pub incremental: bool,
+    /// Memories added, edited or removed since the profile was generated
+    pub pending_memories: usize,
 }
 
-// Bulk import response
+impl ProfileResponse {
+    pub fn from_snapshot(s: ProfileSnapshot, cached: bool, pending_memories: usize) -> Self {
+        Self {
+            profile: s.profile,
+            memory_count: s.memories.len(),
+            strategy: s.strategy,
+            chunks: s.chunks,
+            id: Some(s.id.id.to_string()),
+            model: s.model,
+            generated_at: Some(s.created_at),
+            cached,
+            incremental: s.incremental,
+            pending_memories,
+        }
+    }
+}
+
+// A generated profile, kept so it can be served again and updated
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileSnapshot {
+    pub id: Thing,
+    pub profile: String,
+    pub model: String,
+    pub strategy: Strategy,
+    /// Tags the profile was restricted to, sorted
+    pub tags: Vec<String>,
+    /// Ids of the memories the profile covers
+    pub memories: Vec<String>,
+    pub chunks: usize,
+    pub incremental: bool,
+    /// Snapshot this one replaced
+    pub previous: Option<String>,
+    /// When generation started; memories changed after this aren't covered
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileSnapshotResponse {
+    pub id: String,
+    pub profile: String,
+    pub model: String,
+    pub strategy: Strategy,
+    pub tags: Vec<String>,
+    pub memory_count: usize,
+    pub chunks: usize,
+    pub incremental: bool,
+    pub previous: Option<String>,
+    pub created_at: String,
+}
+
+impl ProfileSnapshotResponse {
+    pub fn from_snapshot(s: ProfileSnapshot) -> Self {
+        Self {
+            id: s.id.id.to_string(),
+            profile: s.profile,
+            model: s.model,
+            strategy: s.strategy,
+            tags: s.tags,
+            memory_count: s.memories.len(),
+            chunks: s.chunks,
+            incremental: s.incremental,
+            previous: s.previous,
+            created_at: s.created_at,
+        }
+    }
+}
+
+// Profile history query params
+#[derive(Debug, Deserialize)]
+pub struct ProfileHistoryQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+// Profile snapshot query params
+#[derive(Debug, Deserialize)]
+pub struct ProfileDiffQuery {
+    /// Snapshot to compare with instead of the one this replaced
+    pub against: Option<String>,
+}
+
+// A profile snapshot and how it differs from another
+#[derive(Debug, Serialize)]
+pub struct ProfileDiffResponse {
+    #[serde(flatten)]
+    pub snapshot: ProfileSnapshotResponse,
+    /// Snapshot the diff is against; absent for the first profile
+    pub compared_to: Option<String>,
+    pub diff: Vec<DiffLine>,
+    /// Memories covered by this snapshot but not the other
+    pub memories_added: Vec<String>,
+    /// Memories covered by the other snapshot but not this one
+    pub memories_removed: Vec<String>,
+}
+
+// One line of a profile diff
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+// API request to answer a question from memories
+#[derive(Debug, Deserialize)]
+pub struct AskRequest {
+    pub question: String,
+    /// Memories to retrieve (default 5)
+    pub limit: Option<usize>,
+    /// Similarity a memory needs to be used; overrides MEMORAI_ASK_MIN_SCORE
+    pub min_score: Option<f32>,
+    /// Only memories with any of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    /// Send Server-Sent Events as the answer is written
+    #[serde(default)]
+    pub stream: bool,
+}
+
+// Answer to a question
+#[derive(Debug, Serialize)]
+pub struct AskResponse {
+    /// Cites memories by id in square brackets
+    pub answer: String,
+    /// The memories the answer cites, in the order first cited
+    pub citations: Vec<SearchResult>,
+    /// No memory was similar enough to the question, so the chat model
+    /// wasn't asked
+    pub refused: bool,
+}
+
+// One line of a JSONL export; on import only `text` is required
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub id: Option<String>,
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embed_model: Option<String>,
+}
+
+impl ExportRecord {
+    pub fn from_memory(m: Memory, include_embedding: bool) -> Self {
+        let (embedding, embed_model) = if include_embedding {
+            (Some(m.embedding), m.embed_model)
+        } else {
+            (None, None)
+        };
+        Self {
+            id: m.id.map(|t| t.id.to_string()),
+            text: m.text,
+            tags: m.tags,
+            source: m.source,
+            created_at: Some(m.created_at),
+            updated_at: Some(m.updated_at),
+            archived_at: m.archived_at,
+            embedding,
+            embed_model,
+        }
+    }
+}
+
+// Export query params
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// Include each memory's embedding and model name
+    #[serde(default)]
+    pub include_embeddings: bool,
+}
+
+// Import query params
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// Replace memories whose id already exists instead of skipping them
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+// Import response
+#[derive(Debug, Default, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    /// Lines whose id already exists
+    pub skipped: usize,
+    /// Imported lines that had to be embedded again (no embedding, or one
+    /// from a different model)
+    pub reembedded: usize,
+    pub failed: usize,
+    pub errors: Vec<String>,
+}
+
+// Database record for a named collection of memories
+#[derive(Debug, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: String,
+}
+
+// API request to create a collection
+#[derive(Debug, Deserialize)]
+pub struct CreateCollectionRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+// API response for a collection
 #[derive(Debug, Serialize)]
+pub struct CollectionResponse {
+    pub name: String,
+    pub description: Option<String>,
+    pub memory_count: usize,
+    pub created_at: String,
+}
+
+// Admin request to re-embed memories
+#[derive(Debug, Deserialize)]
+pub struct ReembedRequest {
+    /// Re-embed every memory, not only those from a different model
+    #[serde(default)]
+    pub all: bool,
+}
+
+// Consolidation job options
+#[derive(Debug, Deserialize)]
+pub struct ConsolidateRequest {
+    #[serde(default = "default_collection")]
+    pub collection: String,
+    /// Cosine similarity to a cluster's first memory needed to join it
+    pub threshold: Option<f32>,
+    /// Largest number of memories merged into one
+    pub max_cluster_size: Option<usize>,
+    /// Archive the originals once their summary is stored
+    #[serde(default)]
+    pub archive: bool,
+}
+
+// Bulk import response
+#[derive(Debug, Default, Serialize)]
 pub struct BulkResponse {
     pub created: usize,
+    pub merged: usize,
+    pub rejected: usize,
     pub failed: usize,
     pub errors: Vec<String>,
+    pub duplicates: Vec<BulkDuplicate>,
 }
 
 // Ollama API types
 #[derive(Debug, Serialize)]
 pub struct OllamaEmbedRequest {
     pub model: String,
-    pub input: String,
+    pub input: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -126,6 +755,25 @@ pub struct OllamaEmbedResponse {
     pub embeddings: Vec<Vec<f32>>,
 }
 
+// OpenAI-compatible embedding API types
+#[derive(Debug, Serialize)]
+pub struct OpenAiEmbedRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiEmbedResponse {
+    pub data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiEmbedding {
+    pub embedding: Vec<f32>,
+    #[serde(default)]
+    pub index: usize,
+}
+
 #[derive(Debug, Serialize)]
 pub struct OllamaGenerateRequest {
     pub model: String,
@@ -133,9 +781,16 @@ pub struct OllamaGenerateRequest {
     pub stream: bool,
 }
 
+// The whole completion, or one line of a streamed one
 #[derive(Debug, Deserialize)]
 pub struct OllamaGenerateResponse {
+    #[serde(default)]
     pub response: String,
+    /// Set on the last line of a stream
+    #[serde(default)]
+    pub done: bool,
+    /// Set instead of `response` when generation fails mid-stream
+    pub error: Option<String>,
 }
 
 // Generic API response wrapper
@@ -1,27 +1,263 @@
+use std::collections::HashSet;
+
 use anyhow::{Context, Result};
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+
+use crate::chat;
+use crate::config::{Config, MIN_TOKEN_BUDGET};
+use crate::db::{self, Db, MemoryFilter};
+use crate::embeddings::cosine_similarity;
+use crate::models::{
+    DiffLine, DiffOp, Memory, MemoryTags, MemoryVector, ProfileDiffResponse, ProfileQuery, ProfileResponse,
+    ProfileSnapshot, ProfileSnapshotResponse,
+};
+use crate::sse::{self, Events};
 
-use crate::config::Config;
-use crate::db::{self, Db};
-use crate::models::{OllamaGenerateRequest, OllamaGenerateResponse};
+/// Rough characters per token, for fitting prompts into the token budget
+/// without running the model's tokenizer
+const CHARS_PER_TOKEN: usize = 4;
 
-pub async fn generate_profile(db: &Db, config: &Config) -> Result<(String, usize)> {
-    let texts = db::get_all_texts(db).await?;
-    let count = texts.len();
+/// Candidates the `diverse` strategy samples per memory it keeps; spreading
+/// over the whole collection would cost time and memory that grow with it
+const DIVERSE_POOL: usize = 4;
+
+/// Which memories a profile is built from when there are more than
+/// `max_memories`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// The newest memories
+    Recent,
+    /// Memories spread across the embedding space, so no topic crowds out
+    /// the others
+    Diverse,
+    /// Memories taken in turn from each tag
+    Tag,
+}
 
-    if count == 0 {
-        return Ok((
-            "No memories stored yet. Add some memories to generate a profile.".to_string(),
-            0,
-        ));
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Recent => "recent",
+            Self::Diverse => "diverse",
+            Self::Tag => "tag",
+        })
     }
+}
 
-    let memories_text = texts
-        .iter()
-        .enumerate()
-        .map(|(i, t)| format!("{}. {}", i + 1, t))
-        .collect::<Vec<_>>()
-        .join("\n");
+impl std::str::FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "recent" => Ok(Self::Recent),
+            "diverse" => Ok(Self::Diverse),
+            "tag" => Ok(Self::Tag),
+            other => anyhow::bail!("Unknown profile strategy '{}' (expected recent, diverse or tag)", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileOptions {
+    pub strategy: Strategy,
+    /// Only memories with any of these tags; with the `tag` strategy, the
+    /// tags to take memories from (every tag when empty). Sorted, since
+    /// profiles are stored per set of tags
+    pub tags: Vec<String>,
+    pub max_memories: usize,
+    /// Tokens of notes sent to the chat model in one prompt
+    pub token_budget: usize,
+    /// Write the profile from scratch even if a stored one is up to date
+    pub refresh: bool,
+}
+
+impl ProfileOptions {
+    /// Options from a profile request, falling back to the configuration.
+    pub fn from_query(query: &ProfileQuery, config: &Config) -> Result<Self> {
+        let mut options = Self {
+            strategy: query.strategy.unwrap_or(config.profile_strategy),
+            tags: query
+                .tags
+                .as_deref()
+                .map(|t| {
+                    t.split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_memories: query.max_memories.unwrap_or(config.profile_max_memories),
+            token_budget: query.token_budget.unwrap_or(config.profile_token_budget),
+            refresh: query.refresh,
+        };
+        options.tags.sort();
+        options.tags.dedup();
+
+        if options.max_memories == 0 {
+            anyhow::bail!("max_memories must be greater than 0");
+        }
+        if options.token_budget < MIN_TOKEN_BUDGET {
+            anyhow::bail!("token_budget must be at least {}", MIN_TOKEN_BUDGET);
+        }
+        Ok(options)
+    }
+}
+
+/// The collection's profile for `options`: the stored one while fewer than
+/// `profile_refresh_after` memories have been added, edited or removed since
+/// it was generated, otherwise that profile updated with those memories. It's
+/// written from scratch when there is none yet, it came from another chat
+/// model, `options.refresh` is set, or there are more changes than an update
+/// can take in. Each new profile is stored.
+///
+/// With `events`, progress and the profile's text are sent there as they
+/// happen.
+pub async fn get_profile(
+    db: &Db,
+    config: &Config,
+    collection: &str,
+    options: &ProfileOptions,
+    events: Option<&Events>,
+) -> Result<ProfileResponse> {
+    // Memories changed while the profile is being written are left for the
+    // next update
+    let started: Datetime = Utc::now().into();
+    let latest = db::latest_profile_snapshot(db, collection, options.strategy, &options.tags).await?;
+
+    let draft = match latest.as_ref().filter(|s| !options.refresh && s.model == config.chat_model) {
+        Some(snapshot) => {
+            let changes = Changes::since(db, collection, options, snapshot).await?;
+            let pending = changes.changed + changes.removed.len();
+            if pending < config.profile_refresh_after {
+                return Ok(ProfileResponse::from_snapshot(snapshot.clone(), true, pending));
+            }
+
+            // An update only sees the notes it's given: more than
+            // `max_memories` would be left out, and it can't tell what the
+            // removed ones said, so once enough pile up it starts over
+            let too_many = changes.changed > options.max_memories;
+            if too_many || changes.removed.len() >= config.profile_refresh_after {
+                generate(db, config, collection, options, events).await?
+            } else {
+                let changed = db::get_memories_filtered(db, &changes.filter).await?;
+                let removed = &changes.removed;
+                Some(update(config, collection, options, snapshot, changed, removed, events).await?)
+            }
+        }
+        None => generate(db, config, collection, options, events).await?,
+    };
+
+    let Some(draft) = draft else {
+        return Ok(ProfileResponse {
+            profile: "No memories stored yet. Add some memories to generate a profile.".to_string(),
+            memory_count: 0,
+            strategy: options.strategy,
+            chunks: 0,
+            id: None,
+            model: config.chat_model.clone(),
+            generated_at: None,
+            cached: false,
+            incremental: false,
+            pending_memories: 0,
+        });
+    };
+
+    let snapshot = db::store_profile_snapshot(
+        db,
+        db::NewProfileSnapshot {
+            collection: collection.to_string(),
+            profile: draft.profile,
+            model: config.chat_model.clone(),
+            strategy: options.strategy,
+            tags: options.tags.clone(),
+            memories: draft.memories,
+            chunks: draft.chunks,
+            incremental: draft.incremental,
+            previous: latest.map(|s| s.id.id.to_string()),
+            created_at: started,
+        },
+    )
+    .await?;
+    Ok(ProfileResponse::from_snapshot(snapshot, false, 0))
+}
+
+/// How the memories `options` cover have changed since a snapshot was
+/// generated
+struct Changes {
+    /// Active memories added or edited since
+    filter: MemoryFilter,
+    changed: usize,
+    /// Memories the snapshot covers that have since been archived, trashed
+    /// or purged
+    removed: HashSet<String>,
+}
+
+impl Changes {
+    async fn since(
+        db: &Db,
+        collection: &str,
+        options: &ProfileOptions,
+        snapshot: &ProfileSnapshot,
+    ) -> Result<Self> {
+        let since = DateTime::parse_from_rfc3339(&snapshot.created_at)
+            .context("Invalid profile timestamp")?
+            .to_utc();
+        let filter = MemoryFilter {
+            collection: Some(collection.to_string()),
+            tags: options.tags.clone(),
+            updated_after: Some(since.into()),
+            ..Default::default()
+        };
+        let changed = db::count_filtered(db, &filter).await?;
+
+        let active = db::active_memory_ids(db, &snapshot.memories).await?;
+        let removed = snapshot
+            .memories
+            .iter()
+            .filter(|id| !active.contains(*id))
+            .cloned()
+            .collect();
+
+        Ok(Self {
+            filter,
+            changed,
+            removed,
+        })
+    }
+}
+
+/// A newly written profile, before it's stored
+struct Draft {
+    profile: String,
+    /// Ids of the memories it covers
+    memories: Vec<String>,
+    chunks: usize,
+    incremental: bool,
+}
+
+/// Write a profile from scratch from the memories `options` select, or
+/// return `None` if there are none.
+///
+/// When the memories don't fit in one prompt they are split into chunks that
+/// do, each chunk is summarised, and the profile is written from the
+/// summaries, merging those first if they don't fit either.
+async fn generate(
+    db: &Db,
+    config: &Config,
+    collection: &str,
+    options: &ProfileOptions,
+    events: Option<&Events>,
+) -> Result<Option<Draft>> {
+    let memories = select(db, config, collection, options).await?;
+    if memories.is_empty() {
+        return Ok(None);
+    }
+
+    let ids = memory_ids(&memories);
+    let (notes, chunks) = condense(config, collection, memories, options.token_budget, events).await?;
 
     let prompt = format!(
         "Based on the following collection of memories/notes from a person, create a concise user profile summary. \
@@ -29,34 +265,378 @@ pub async fn generate_profile(db: &Db, config: &Config) -> Result<(String, usize
          Be insightful but respectful of privacy. Write in third person.\n\n\
          Memories:\n{}\n\n\
          Profile summary:",
-        memories_text
+        notes
     );
 
-    let client = Client::new();
-    let url = format!("{}/api/generate", config.ollama_url);
-    let request = OllamaGenerateRequest {
-        model: config.chat_model.clone(),
-        prompt,
-        stream: false,
-    };
+    sse::progress(events, "Writing the profile".to_string()).await?;
+    let profile = sse::generate(config, prompt, events)
+        .await
+        .context("Profile generation failed")?;
 
-    let response = client
-        .post(&url)
-        .json(&request)
-        .send()
+    Ok(Some(Draft {
+        profile,
+        memories: ids,
+        chunks,
+        incremental: false,
+    }))
+}
+
+/// Revise a stored profile with the memories added or edited since, and
+/// stop counting the `removed` ones among those it covers.
+async fn update(
+    config: &Config,
+    collection: &str,
+    options: &ProfileOptions,
+    snapshot: &ProfileSnapshot,
+    changed: Vec<Memory>,
+    removed: &HashSet<String>,
+    events: Option<&Events>,
+) -> Result<Draft> {
+    let mut seen = HashSet::new();
+    let ids = snapshot
+        .memories
+        .iter()
+        .filter(|id| !removed.contains(*id))
+        .cloned()
+        .chain(memory_ids(&changed))
+        .filter(|id| seen.insert(id.clone()))
+        .collect();
+
+    let count = changed.len();
+    let (notes, chunks) = condense(config, collection, changed, options.token_budget, events).await?;
+
+    let prompt = format!(
+        "Below is a profile of a person, followed by notes they have written or edited since it was written. \
+         Update the profile with what the new notes reveal: add new interests, expertise and patterns, \
+         and revise anything the notes contradict, keeping the rest. \
+         Write in third person and reply with the updated profile only.\n\n\
+         Current profile:\n{}\n\n\
+         New notes:\n{}\n\n\
+         Updated profile:",
+        snapshot.profile, notes
+    );
+
+    sse::progress(events, format!("Updating the profile with {} new memories", count)).await?;
+    let profile = sse::generate(config, prompt, events)
         .await
-        .context("Failed to connect to Ollama for profile generation")?;
+        .context("Profile update failed")?;
+
+    Ok(Draft {
+        profile,
+        memories: ids,
+        chunks,
+        incremental: true,
+    })
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        anyhow::bail!("Ollama generate request failed ({}): {}", status, body);
+/// The memories as notes for a prompt, oldest first so the model sees how
+/// things changed over time. When they don't fit in `token_budget` tokens,
+/// they're summarised a chunk at a time and the summaries merged. Returns the
+/// notes and how many chunks they took.
+async fn condense(
+    config: &Config,
+    collection: &str,
+    mut memories: Vec<Memory>,
+    token_budget: usize,
+    events: Option<&Events>,
+) -> Result<(String, usize)> {
+    memories.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    let budget = token_budget * CHARS_PER_TOKEN;
+    let notes: Vec<String> = memories
+        .iter()
+        .map(|m| {
+            let date = m.created_at.get(..10).unwrap_or(&m.created_at);
+            truncate(&format!("- ({}) {}", date, m.text), budget)
+        })
+        .collect();
+
+    let chunks = pack(&notes, budget);
+    let chunk_count = chunks.len();
+    if chunk_count == 1 {
+        return Ok((chunks.into_iter().next().unwrap_or_default(), 1));
     }
 
-    let gen_response: OllamaGenerateResponse = response
-        .json()
-        .await
-        .context("Failed to parse Ollama generate response")?;
+    tracing::info!(
+        "Summarising {} memories from '{}' in {} chunks",
+        memories.len(),
+        collection,
+        chunk_count
+    );
+
+    let mut summaries = Vec::with_capacity(chunk_count);
+    for (i, chunk) in chunks.iter().enumerate() {
+        sse::progress(events, format!("Summarising chunk {} of {}", i + 1, chunk_count)).await?;
+        let summary = chat::generate(config, summary_prompt(chunk, i + 1, chunk_count))
+            .await
+            .with_context(|| format!("Summarising chunk {} of {} failed", i + 1, chunk_count))?;
+        summaries.push(summary);
+    }
+    Ok((reduce(config, summaries, budget, events).await?, chunk_count))
+}
+
+fn memory_ids(memories: &[Memory]) -> Vec<String> {
+    memories
+        .iter()
+        .filter_map(|m| m.id.as_ref().map(|t| t.id.to_string()))
+        .collect()
+}
+
+/// Merge chunk summaries until they fit in one prompt.
+async fn reduce(
+    config: &Config,
+    mut summaries: Vec<String>,
+    budget: usize,
+    events: Option<&Events>,
+) -> Result<String> {
+    while summaries.iter().map(|s| s.len() + 2).sum::<usize>() > budget {
+        if let [summary] = summaries.as_slice() {
+            return Ok(truncate(summary, budget));
+        }
+
+        // Halving each summary guarantees at least two per group, so every
+        // round leaves fewer of them
+        let halved: Vec<String> = summaries.iter().map(|s| truncate(s, budget / 2 - 2)).collect();
+        let groups = pack(&halved, budget);
+        let message = format!("Merging {} summaries into {}", summaries.len(), groups.len());
+        sse::progress(events, message).await?;
+
+        summaries = Vec::with_capacity(groups.len());
+        for group in groups {
+            let prompt = format!(
+                "The following are partial summaries of notes written by one person. \
+                 Merge them into a single summary that keeps every distinct fact about the person \
+                 and drops repetition. Reply with the summary only.\n\n\
+                 Summaries:\n{}\n\n\
+                 Merged summary:",
+                group
+            );
+            summaries.push(chat::generate(config, prompt).await.context("Merging summaries failed")?);
+        }
+    }
+
+    Ok(summaries.join("\n\n"))
+}
+
+fn summary_prompt(notes: &str, part: usize, parts: usize) -> String {
+    format!(
+        "The following notes were written by one person (part {} of {}). \
+         List what they reveal about the person: interests, expertise, preferences, personality traits \
+         and recurring patterns. Be concise and factual, and reply with the list only.\n\n\
+         Notes:\n{}\n\n\
+         What the notes reveal:",
+        part, parts, notes
+    )
+}
+
+/// Join items into as few newline-separated chunks of at most `budget`
+/// bytes as possible, keeping their order.
+fn pack(items: &[String], budget: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for item in items {
+        if !current.is_empty() && current.len() + 1 + item.len() > budget {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(item);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// `text` cut to at most `max` bytes at a character boundary.
+fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max.saturating_sub(3);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &text[..end])
+}
+
+/// The memories a profile covers, at most `max_memories` of them.
+///
+/// Only the fields a strategy chooses by are read until the choice is made,
+/// so the rest of each memory is loaded for the chosen ones alone.
+async fn select(
+    db: &Db,
+    config: &Config,
+    collection: &str,
+    options: &ProfileOptions,
+) -> Result<Vec<Memory>> {
+    let filter = MemoryFilter {
+        collection: Some(collection.to_string()),
+        tags: options.tags.clone(),
+        ..Default::default()
+    };
 
-    Ok((gen_response.response.trim().to_string(), count))
+    let chosen = match options.strategy {
+        Strategy::Recent => return db::get_memories_paginated(db, 1, options.max_memories, &filter).await,
+        Strategy::Diverse => {
+            // Vectors from another model aren't comparable
+            let filter = MemoryFilter {
+                embed_model: Some(config.embedding_model()),
+                ..filter
+            };
+            let max = options.max_memories;
+            let pool = db::sample_vectors(db, &filter, max.saturating_mul(DIVERSE_POOL)).await?;
+            tokio::task::spawn_blocking(move || spread(pool, max))
+                .await
+                .context("Selecting memories failed")?
+        }
+        Strategy::Tag => {
+            let tags = if options.tags.is_empty() {
+                db::get_tag_counts(db, collection)
+                    .await?
+                    .into_iter()
+                    .map(|(tag, _)| tag)
+                    .collect()
+            } else {
+                options.tags.clone()
+            };
+            let memories = db::get_memory_tags(db, &filter).await?;
+            round_robin(memories, &tags, options.max_memories)
+        }
+    };
+    db::get_memories_by_ids(db, chosen).await
+}
+
+/// Farthest-point sampling: start from the newest memory, then repeatedly
+/// take the one least similar to everything taken so far.
+fn spread(mut memories: Vec<MemoryVector>, max: usize) -> Vec<Thing> {
+    if memories.len() <= max {
+        return memories.into_iter().map(|m| m.id).collect();
+    }
+    memories.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    // Highest similarity of each memory to any selected one
+    let mut closest = vec![f32::MIN; memories.len()];
+    let mut selected = vec![false; memories.len()];
+    let mut next = 0;
+
+    for _ in 0..max {
+        selected[next] = true;
+        let chosen = &memories[next].embedding;
+        for (i, memory) in memories.iter().enumerate() {
+            if !selected[i] {
+                closest[i] = closest[i].max(cosine_similarity(chosen, &memory.embedding));
+            }
+        }
+
+        next = match (0..memories.len())
+            .filter(|&i| !selected[i])
+            .min_by(|&a, &b| closest[a].total_cmp(&closest[b]))
+        {
+            Some(i) => i,
+            None => break,
+        };
+    }
+
+    memories
+        .into_iter()
+        .zip(selected)
+        .filter_map(|(m, keep)| keep.then_some(m.id))
+        .collect()
+}
+
+/// Take memories from each tag in turn, newest first within a tag, so every
+/// tag is represented before any gets a second memory. `memories` must be
+/// sorted newest first.
+fn round_robin(memories: Vec<MemoryTags>, tags: &[String], max: usize) -> Vec<Thing> {
+    let mut queues: Vec<Vec<usize>> = tags
+        .iter()
+        .map(|tag| {
+            memories
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.tags.contains(tag))
+                .map(|(i, _)| i)
+                .rev()
+                .collect()
+        })
+        .collect();
+
+    let mut taken = HashSet::new();
+    let mut order = Vec::new();
+    while order.len() < max && queues.iter().any(|q| !q.is_empty()) {
+        for queue in &mut queues {
+            // Skip memories already taken for another of their tags
+            while let Some(i) = queue.pop() {
+                if taken.insert(i) {
+                    order.push(i);
+                    break;
+                }
+            }
+            if order.len() == max {
+                break;
+            }
+        }
+    }
+
+    let mut memories: Vec<Option<MemoryTags>> = memories.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| memories[i].take().map(|m| m.id)).collect()
+}
+
+/// How `snapshot` differs from `base`, or from nothing for the first
+/// profile.
+pub fn compare(snapshot: ProfileSnapshot, base: Option<ProfileSnapshot>) -> ProfileDiffResponse {
+    let (old_profile, old_memories) = base
+        .as_ref()
+        .map(|b| (b.profile.as_str(), b.memories.as_slice()))
+        .unwrap_or_default();
+    let old: HashSet<&String> = old_memories.iter().collect();
+    let new: HashSet<&String> = snapshot.memories.iter().collect();
+
+    ProfileDiffResponse {
+        diff: diff(old_profile, &snapshot.profile),
+        memories_added: snapshot.memories.iter().filter(|m| !old.contains(m)).cloned().collect(),
+        memories_removed: old_memories.iter().filter(|m| !new.contains(m)).cloned().collect(),
+        compared_to: base.map(|b| b.id.id.to_string()),
+        snapshot: ProfileSnapshotResponse::from_snapshot(snapshot),
+    }
+}
+
+/// Line diff turning `old` into `new`, from their longest common
+/// subsequence of lines.
+fn diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine { op, text: text.to_string() };
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(line(DiffOp::Unchanged, old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(line(DiffOp::Removed, old[i]));
+            i += 1;
+        } else {
+            lines.push(line(DiffOp::Added, new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| line(DiffOp::Removed, l)));
+    lines.extend(new[j..].iter().map(|l| line(DiffOp::Added, l)));
+    lines
 }
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::db::{self, Db};
+use crate::embeddings::EmbeddingClient;
+use crate::jobs::Jobs;
+
+/// Re-embed memories produced by a different model than the configured one
+/// (or every memory with `all`), reporting progress on the job `job_id`.
+///
+/// If the vector index has the wrong dimension, the index can't take the new
+/// vectors, so they are staged beside the old ones. Once every memory has
+/// one they are swapped in and the index is rebuilt; if any memory fails,
+/// the staged vectors are thrown away and nothing changes.
+pub async fn run(
+    db: Db,
+    embeddings: Arc<EmbeddingClient>,
+    jobs: Jobs,
+    job_id: String,
+    embed_dim: usize,
+    all: bool,
+) {
+    let result = reembed(&db, &embeddings, &jobs, &job_id, embed_dim, all).await;
+    if let Err(err) = &result {
+        tracing::error!("Re-embedding failed: {:#}", err);
+    }
+    jobs.finish(&job_id, result);
+}
+
+async fn reembed(
+    db: &Db,
+    embeddings: &EmbeddingClient,
+    jobs: &Jobs,
+    job_id: &str,
+    embed_dim: usize,
+    all: bool,
+) -> Result<()> {
+    let model = embeddings.model().to_string();
+    let memories = db::get_stale_memories(db, &model, all).await?;
+    jobs.set_total(job_id, memories.len());
+    tracing::info!("Re-embedding {} memories with {}", memories.len(), model);
+
+    let old_dim = db::vector_index_dimension(db).await?;
+    let rebuild_index = old_dim != Some(embed_dim);
+    let mut failed = 0;
+
+    // Hand the embedding client enough work to keep every request slot busy
+    for chunk in memories.chunks(embeddings.batch_size() * embeddings.concurrency()) {
+        let texts: Vec<String> = chunk.iter().map(|m| m.text.clone()).collect();
+        let results = embeddings.embed_batch(&texts).await;
+
+        for (memory, embedding) in chunk.iter().zip(results) {
+            let id = memory.id.id.to_string();
+            let stored = match embedding {
+                Ok(e) if e.len() != embed_dim => Err(anyhow::anyhow!(
+                    "model returned {} dimensions but MEMORAI_EMBED_DIM is {}",
+                    e.len(),
+                    embed_dim
+                )),
+                Ok(e) if rebuild_index => db::stage_embedding(db, memory.id.clone(), e, &model).await,
+                Ok(e) => db::set_embedding(db, memory.id.clone(), e, &model).await,
+                Err(err) => Err(err),
+            };
+
+            match stored {
+                Ok(()) => jobs.record_success(job_id),
+                Err(err) => {
+                    failed += 1;
+                    jobs.record_failure(job_id, format!("{}: {:#}", id, err));
+                }
+            }
+        }
+    }
+
+    if !rebuild_index {
+        return Ok(());
+    }
+    if failed > 0 {
+        db::clear_staged_embeddings(db).await?;
+        anyhow::bail!(
+            "{} memories could not be re-embedded, so none were changed and the vector index was kept; run the job again once they can be",
+            failed
+        );
+    }
+
+    tracing::info!("Rebuilding vector index with dimension {}", embed_dim);
+    db::remove_vector_index(db).await?;
+    if let Err(err) = db::apply_staged_embeddings(db).await {
+        // The swap is one statement, so the old vectors are all still there
+        if let Some(old_dim) = old_dim {
+            db::define_vector_index(db, old_dim).await?;
+        }
+        return Err(err);
+    }
+    db::define_vector_index(db, embed_dim).await.with_context(|| {
+        format!(
+            "Embeddings were replaced but the vector index could not be rebuilt with dimension {}; search uses exact scoring until it is",
+            embed_dim
+        )
+    })
+}
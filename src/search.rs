@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::db::{self, Db, MemoryFilter, MemoryState};
+use crate::embeddings::{cosine_similarity, EmbeddingClient};
+use crate::models::{Memory, MemoryResponse, SearchMode, SearchQuery, SearchResult, TagMatch};
+
+/// Reciprocal rank fusion constant; larger values flatten the advantage of top ranks.
+const RRF_K: f32 = 60.0;
+
+/// Build the database filter for a search request, validating its dates.
+pub fn filter_from_query(query: &SearchQuery) -> Result<MemoryFilter> {
+    let tags = query
+        .tags
+        .as_deref()
+        .map(|t| {
+            t.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(MemoryFilter {
+        collection: None,
+        tags,
+        match_all_tags: query.tag_match == TagMatch::All,
+        source: query.source.clone(),
+        created_after: parse_date("created_after", query.created_after.as_deref())?,
+        created_before: parse_date("created_before", query.created_before.as_deref())?,
+        updated_after: parse_date("updated_after", query.updated_after.as_deref())?,
+        updated_before: parse_date("updated_before", query.updated_before.as_deref())?,
+        embed_model: None,
+        state: MemoryState::Active,
+    })
+}
+
+/// Accepts RFC 3339 timestamps or plain dates (midnight UTC).
+fn parse_date(name: &str, value: Option<&str>) -> Result<Option<surrealdb::sql::Datetime>> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let parsed = match DateTime::parse_from_rfc3339(value) {
+        Ok(dt) => dt.with_timezone(&Utc),
+        Err(_) => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|dt| dt.and_utc())
+            .with_context(|| format!("Invalid {}: expected RFC 3339 or YYYY-MM-DD", name))?,
+    };
+
+    Ok(Some(parsed.into()))
+}
+
+pub async fn search(
+    db: &Db,
+    embeddings: &EmbeddingClient,
+    query: &SearchQuery,
+    filter: &MemoryFilter,
+) -> Result<Vec<SearchResult>> {
+    let limit = query.limit.unwrap_or(5).min(50);
+
+    let min_score = query.min_score.unwrap_or(f32::MIN);
+    let above = |(_, score): &(Memory, f32)| *score >= min_score;
+
+    let hits: Vec<(Memory, f32)> = match query.mode {
+        SearchMode::Vector => {
+            let hits = vector_hits(db, embeddings, &query.q, limit, query.exact, filter).await?;
+            hits.into_iter().filter(above).collect()
+        }
+        SearchMode::Keyword => {
+            let hits = db::search_keyword(db, &query.q, limit, filter).await?;
+            hits.into_iter().filter(above).collect()
+        }
+        SearchMode::Hybrid => {
+            // Fuse over a wider candidate pool than we return
+            let pool = (limit * 4).max(20);
+            let vector = vector_hits(db, embeddings, &query.q, pool, query.exact, filter).await?;
+            let keyword = db::search_keyword(db, &query.q, pool, filter).await?;
+            // Fused scores are around 1/60 per ranking, so the threshold applies
+            // to the cosine similarity of vector matches before fusing
+            reciprocal_rank_fusion(
+                vector.into_iter().filter(above).collect(),
+                keyword,
+                query.vector_weight.unwrap_or(1.0),
+                query.keyword_weight.unwrap_or(1.0),
+            )
+        }
+    };
+
+    Ok(hits
+        .into_iter()
+        .take(limit)
+        .map(|(memory, score)| SearchResult {
+            memory: MemoryResponse::from_memory(memory),
+            score,
+        })
+        .collect())
+}
+
+/// Memories ranked by cosine similarity to the query, best first.
+async fn vector_hits(
+    db: &Db,
+    embeddings: &EmbeddingClient,
+    query: &str,
+    limit: usize,
+    exact: bool,
+    filter: &MemoryFilter,
+) -> Result<Vec<(Memory, f32)>> {
+    let query_embedding = embeddings.embed(query).await?;
+
+    // Vectors from another model aren't comparable with the query
+    let filter = MemoryFilter {
+        embed_model: Some(embeddings.model().to_string()),
+        ..filter.clone()
+    };
+
+    let memories = if exact {
+        db::get_memories_filtered(db, &filter).await?
+    } else {
+        match db::search_nearest(db, query_embedding.clone(), limit, &filter).await {
+            Ok(memories) => memories,
+            Err(err) => {
+                // The index is dropped while `reembed` rebuilds it
+                tracing::warn!("Vector index unavailable, using exact scoring: {:#}", err);
+                db::get_memories_filtered(db, &filter).await?
+            }
+        }
+    };
+
+    let mut scored: Vec<(Memory, f32)> = memories
+        .into_iter()
+        .map(|m| {
+            let score = cosine_similarity(&query_embedding, &m.embedding);
+            (m, score)
+        })
+        .collect();
+
+    sort_by_score(&mut scored);
+    scored.truncate(limit);
+    Ok(scored)
+}
+
+fn reciprocal_rank_fusion(
+    vector: Vec<(Memory, f32)>,
+    keyword: Vec<(Memory, f32)>,
+    vector_weight: f32,
+    keyword_weight: f32,
+) -> Vec<(Memory, f32)> {
+    let mut fused: HashMap<String, (Memory, f32)> = HashMap::new();
+
+    for (weight, hits) in [(vector_weight, vector), (keyword_weight, keyword)] {
+        for (rank, (memory, _)) in hits.into_iter().enumerate() {
+            let key = memory.id.as_ref().map(|t| t.to_string()).unwrap_or_default();
+            let contribution = weight / (RRF_K + rank as f32 + 1.0);
+            fused.entry(key).or_insert((memory, 0.0)).1 += contribution;
+        }
+    }
+
+    let mut scored: Vec<(Memory, f32)> = fused.into_values().collect();
+    sort_by_score(&mut scored);
+    scored
+}
+
+fn sort_by_score(scored: &mut [(Memory, f32)]) {
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+}
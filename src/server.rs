@@ -1,30 +1,142 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_cors::Cors;
+use actix_web::dev::{HttpServiceFactory, Payload};
+use actix_web::http::header;
+use actix_web::{error, middleware, web, FromRequest, HttpRequest, HttpResponse, Scope};
+use futures::future::LocalBoxFuture;
+use serde::Deserialize;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::ask;
+use crate::auth::{self, Access, Actor, ApiKeys};
+use crate::autotag::Tagger;
 use crate::config::Config;
-use crate::db::{self, Db};
-use crate::embeddings::{cosine_similarity, EmbeddingClient};
+use crate::consolidate;
+use crate::db::{self, Db, MemoryState};
+use crate::dedup::{self, Written};
+use crate::embeddings::EmbeddingClient;
+use crate::ingest;
+use crate::jobs::Jobs;
+use crate::mcp::McpServer;
 use crate::models::*;
 use crate::profile;
+use crate::reembed;
+use crate::sse;
+use crate::transfer;
 
 pub struct AppState {
     pub db: Db,
     pub config: Config,
-    pub embeddings: EmbeddingClient,
+    pub embeddings: Arc<EmbeddingClient>,
+    pub jobs: Jobs,
+    pub keys: ApiKeys,
 }
 
 pub type SharedState = web::Data<Arc<RwLock<AppState>>>;
 
-pub fn api_scope() -> Scope {
-    web::scope("/v1")
+/// The collection a request operates on: `{collection}` from
+/// `/v1/collections/{collection}/...`, or the default collection for the
+/// unscoped `/v1` routes. Rejects collections that don't exist.
+pub struct CollectionName(pub String);
+
+impl FromRequest for CollectionName {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let name = req
+            .match_info()
+            .get("collection")
+            .unwrap_or(db::DEFAULT_COLLECTION)
+            .to_string();
+        let state = req.app_data::<SharedState>().cloned();
+
+        Box::pin(async move {
+            let state = state.ok_or_else(|| error::ErrorInternalServerError("Missing app state"))?;
+            let exists = db::collection_exists(&state.read().await.db, &name)
+                .await
+                .map_err(|err| error::ErrorInternalServerError(format!("{:#}", err)))?;
+
+            if !exists {
+                let response = HttpResponse::NotFound()
+                    .json(ApiResponse::<()>::error(format!("Collection '{}' not found", name)));
+                return Err(error::InternalError::from_response("collection not found", response).into());
+            }
+            Ok(CollectionName(name))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct MemoryPath {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct SnapshotPath {
+    id: String,
+}
+
+pub fn api_scope() -> impl HttpServiceFactory {
+    memory_routes(web::scope("/v1"))
+        .wrap(middleware::from_fn(auth::require_api_key))
+        .route("/collections", web::post().to(create_collection))
+        .route("/collections", web::get().to(list_collections))
+        .route("/collections/{collection}", web::delete().to(delete_collection))
+        .service(memory_routes(web::scope("/collections/{collection}")))
+        .route("/admin/reembed", web::post().to(start_reembed))
+        .route("/admin/consolidate", web::post().to(start_consolidate))
+        .route("/jobs", web::get().to(list_jobs))
+        .route("/jobs/{id}", web::get().to(get_job))
+}
+
+/// Routes that act on a single collection's memories
+fn memory_routes(scope: Scope) -> Scope {
+    scope
         .route("/memories", web::post().to(create_memory))
         .route("/memories", web::get().to(list_memories))
         .route("/memories/bulk", web::post().to(bulk_create))
+        .route("/memories/export", web::get().to(export_memories))
+        .route("/memories/import", web::post().to(import_memories))
+        .route("/memories/{id}", web::get().to(get_memory))
+        .route("/memories/{id}", web::patch().to(update_memory))
         .route("/memories/{id}", web::delete().to(delete_memory))
+        .route("/memories/{id}/restore", web::post().to(restore_memory))
+        .route("/memories/{id}/history", web::get().to(memory_history))
+        .route("/memories/{id}/revert", web::post().to(revert_memory))
+        .route("/trash", web::get().to(list_trash))
+        .route("/trash", web::delete().to(purge_trash))
+        .service(
+            web::resource("/documents")
+                .app_data(web::JsonConfig::default().limit(ingest::MAX_DOCUMENT_BYTES))
+                .route(web::post().to(ingest_document)),
+        )
         .route("/search", web::get().to(search))
         .route("/stats", web::get().to(stats))
         .route("/profile", web::get().to(get_profile))
+        .route("/profile/history", web::get().to(profile_history))
+        .route("/profile/history/{id}", web::get().to(profile_snapshot))
+        .route("/ask", web::post().to(ask_question))
+        .service(
+            web::resource("/mcp")
+                .route(web::post().to(mcp))
+                .default_service(web::to(HttpResponse::MethodNotAllowed)),
+        )
+}
+
+/// CORS policy for the configured origins. With none, cross-origin browser
+/// requests are refused; `*` allows any origin.
+pub fn cors_policy(origins: &[String]) -> Cors {
+    if origins.iter().any(|o| o == "*") {
+        return Cors::permissive();
+    }
+
+    origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allowed_methods(["GET", "POST", "PATCH", "DELETE"])
+        .allowed_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+        .max_age(3600)
 }
 
 pub fn health_route() -> actix_web::Resource {
@@ -37,15 +149,18 @@ async fn health() -> HttpResponse {
 
 async fn create_memory(
     state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
     body: web::Json<CreateMemoryRequest>,
 ) -> HttpResponse {
-    let req = body.into_inner();
+    let mut req = body.into_inner();
 
     if req.text.trim().is_empty() {
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Text cannot be empty"));
     }
 
     let state = state.read().await;
+    Tagger::new(&state.db, &state.config, &collection.0).tag(&mut req).await;
 
     let embedding = match state.embeddings.embed(&req.text).await {
         Ok(e) => e,
@@ -56,9 +171,46 @@ async fn create_memory(
         }
     };
 
-    match db::create_memory(&state.db, req.text, req.tags, req.source, embedding).await {
-        Ok(memory) => HttpResponse::Created()
-            .json(ApiResponse::success(MemoryResponse::from_memory(memory))),
+    match dedup::create_memory(
+        &state.db,
+        &collection.0,
+        req,
+        embedding,
+        state.embeddings.model(),
+        state.config.dedup(),
+        actor.0.as_deref(),
+    )
+    .await
+    {
+        Ok(Written::Created(memory)) => HttpResponse::Created().json(ApiResponse::success(
+            CreateMemoryResponse {
+                memory: MemoryResponse::from_memory(memory),
+                outcome: WriteOutcome::Created,
+                similarity: None,
+            },
+        )),
+        Ok(Written::Merged(memory, similarity)) => HttpResponse::Ok().json(ApiResponse::success(
+            CreateMemoryResponse {
+                memory: MemoryResponse::from_memory(memory),
+                outcome: WriteOutcome::Merged,
+                similarity: Some(similarity),
+            },
+        )),
+        Ok(Written::Rejected(existing, similarity)) => {
+            let existing = MemoryResponse::from_memory(existing);
+            HttpResponse::Conflict().json(ApiResponse {
+                ok: false,
+                error: Some(format!(
+                    "Duplicate of memory {} (similarity {:.3})",
+                    existing.id, similarity
+                )),
+                data: Some(CreateMemoryResponse {
+                    memory: existing,
+                    outcome: WriteOutcome::Rejected,
+                    similarity: Some(similarity),
+                }),
+            })
+        }
         Err(err) => {
             tracing::error!("Failed to create memory: {}", err);
             HttpResponse::InternalServerError()
@@ -69,21 +221,26 @@ async fn create_memory(
 
 async fn list_memories(
     state: SharedState,
+    collection: CollectionName,
     query: web::Query<ListQuery>,
 ) -> HttpResponse {
     let page = query.page.unwrap_or(1);
     let per_page = query.per_page.unwrap_or(20).min(100);
     let state = state.read().await;
 
-    match db::get_memories_paginated(
-        &state.db,
-        page,
-        per_page,
-        query.tag.as_deref(),
-        query.source.as_deref(),
-    )
-    .await
-    {
+    let filter = db::MemoryFilter {
+        collection: Some(collection.0),
+        tags: query.tag.iter().cloned().collect(),
+        source: query.source.clone(),
+        state: if query.archived {
+            MemoryState::Archived
+        } else {
+            MemoryState::Active
+        },
+        ..Default::default()
+    };
+
+    match db::get_memories_paginated(&state.db, page, per_page, &filter).await {
         Ok(memories) => {
             let responses: Vec<MemoryResponse> =
                 memories.into_iter().map(MemoryResponse::from_memory).collect();
@@ -94,16 +251,111 @@ async fn list_memories(
     }
 }
 
+async fn get_memory(
+    state: SharedState,
+    collection: CollectionName,
+    path: web::Path<MemoryPath>,
+    query: web::Query<GetMemoryQuery>,
+) -> HttpResponse {
+    let id = path.into_inner().id;
+    let state = state.read().await;
+
+    match db::get_memory(&state.db, &collection.0, &id).await {
+        Ok(Some(memory)) => {
+            let response = if query.include_embedding {
+                MemoryResponse::with_embedding(memory)
+            } else {
+                MemoryResponse::from_memory(memory)
+            };
+            HttpResponse::Ok().json(ApiResponse::success(response))
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to fetch memory: {}", err))),
+    }
+}
+
+async fn update_memory(
+    state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
+    path: web::Path<MemoryPath>,
+    body: web::Json<UpdateMemoryRequest>,
+) -> HttpResponse {
+    let id = path.into_inner().id;
+    let req = body.into_inner();
+
+    if req.text.as_deref().is_some_and(|t| t.trim().is_empty()) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Text cannot be empty"));
+    }
+
+    let state = state.read().await;
+
+    let existing = match db::get_memory(&state.db, &collection.0, &id).await {
+        Ok(Some(m)) if m.deleted_at.is_some() => {
+            return HttpResponse::Conflict()
+                .json(ApiResponse::<()>::error("Memory is in the trash; restore it first"));
+        }
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"));
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error(format!("Failed to fetch memory: {}", err)));
+        }
+    };
+
+    // Only re-embed when the text actually changes
+    let text = req.text.filter(|t| *t != existing.text);
+    let embedding = match &text {
+        Some(t) => match state.embeddings.embed(t).await {
+            Ok(e) => Some(e),
+            Err(err) => {
+                tracing::error!("Embedding failed: {}", err);
+                return HttpResponse::InternalServerError()
+                    .json(ApiResponse::<()>::error(format!("Embedding failed: {}", err)));
+            }
+        },
+        None => None,
+    };
+
+    let update = db::MemoryUpdate {
+        text,
+        tags: req.tags,
+        source: req.source,
+        embedding: embedding.map(|e| (e, state.embeddings.model().to_string())),
+    };
+
+    match db::update_memory(&state.db, &collection.0, &id, update, actor.0.as_deref()).await {
+        Ok(Some(memory)) => {
+            HttpResponse::Ok().json(ApiResponse::success(MemoryResponse::from_memory(memory)))
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"))
+        }
+        Err(err) => {
+            tracing::error!("Failed to update memory: {}", err);
+            HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error(format!("Failed to update memory: {}", err)))
+        }
+    }
+}
+
 async fn delete_memory(
     state: SharedState,
-    path: web::Path<String>,
+    collection: CollectionName,
+    actor: Actor,
+    path: web::Path<MemoryPath>,
 ) -> HttpResponse {
-    let id = path.into_inner();
+    let id = path.into_inner().id;
     let state = state.read().await;
 
-    match db::delete_memory(&state.db, &id).await {
+    match db::delete_memory(&state.db, &collection.0, &id, actor.0.as_deref()).await {
         Ok(Some(_)) => {
-            HttpResponse::Ok().json(ApiResponse::success("Memory deleted".to_string()))
+            HttpResponse::Ok().json(ApiResponse::success("Memory moved to trash".to_string()))
         }
         Ok(None) => {
             HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"))
@@ -113,55 +365,216 @@ async fn delete_memory(
     }
 }
 
-async fn search(
+async fn restore_memory(
     state: SharedState,
-    query: web::Query<SearchQuery>,
+    collection: CollectionName,
+    actor: Actor,
+    path: web::Path<MemoryPath>,
 ) -> HttpResponse {
-    if query.q.trim().is_empty() {
-        return HttpResponse::BadRequest()
-            .json(ApiResponse::<()>::error("Query cannot be empty"));
+    let id = path.into_inner().id;
+    let state = state.read().await;
+
+    match db::restore_memory(&state.db, &collection.0, &id, actor.0.as_deref()).await {
+        Ok(Some(memory)) => {
+            HttpResponse::Ok().json(ApiResponse::success(MemoryResponse::from_memory(memory)))
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found in trash"))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to restore memory: {}", err))),
     }
+}
 
-    let limit = query.limit.unwrap_or(5).min(50);
+async fn memory_history(
+    state: SharedState,
+    collection: CollectionName,
+    path: web::Path<MemoryPath>,
+) -> HttpResponse {
+    let id = path.into_inner().id;
     let state = state.read().await;
 
-    let query_embedding = match state.embeddings.embed(&query.q).await {
-        Ok(e) => e,
+    match db::get_memory(&state.db, &collection.0, &id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"));
+        }
         Err(err) => {
             return HttpResponse::InternalServerError()
-                .json(ApiResponse::<()>::error(format!("Embedding failed: {}", err)));
+                .json(ApiResponse::<()>::error(format!("Failed to fetch memory: {}", err)));
+        }
+    }
+
+    match db::get_revisions(&state.db, &id).await {
+        Ok(revisions) => {
+            let responses: Vec<RevisionResponse> =
+                revisions.into_iter().map(RevisionResponse::from_revision).collect();
+            HttpResponse::Ok().json(ApiResponse::success(responses))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to fetch history: {}", err))),
+    }
+}
+
+/// Restore a memory's text, tags and source from one of its revisions. The
+/// revert is itself recorded as a new revision.
+async fn revert_memory(
+    state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
+    path: web::Path<MemoryPath>,
+    body: web::Json<RevertRequest>,
+) -> HttpResponse {
+    let id = path.into_inner().id;
+    let state = state.read().await;
+
+    let existing = match db::get_memory(&state.db, &collection.0, &id).await {
+        Ok(Some(m)) if m.deleted_at.is_some() => {
+            return HttpResponse::Conflict()
+                .json(ApiResponse::<()>::error("Memory is in the trash; restore it first"));
+        }
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"));
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error(format!("Failed to fetch memory: {}", err)));
         }
     };
 
-    let memories = match db::get_all_memories(&state.db).await {
-        Ok(m) => m,
+    let revision = match db::get_revision(&state.db, &id, &body.revision).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()>::error("Revision not found"));
+        }
         Err(err) => {
             return HttpResponse::InternalServerError()
-                .json(ApiResponse::<()>::error(format!("Failed to fetch memories: {}", err)));
+                .json(ApiResponse::<()>::error(format!("Failed to fetch revision: {}", err)));
         }
     };
 
-    let mut scored: Vec<SearchResult> = memories
-        .into_iter()
-        .map(|m| {
-            let score = cosine_similarity(&query_embedding, &m.embedding);
-            SearchResult {
-                memory: MemoryResponse::from_memory(m),
-                score,
+    let text = Some(revision.text).filter(|t| *t != existing.text);
+    let embedding = match &text {
+        Some(t) => match state.embeddings.embed(t).await {
+            Ok(e) => Some(e),
+            Err(err) => {
+                tracing::error!("Embedding failed: {}", err);
+                return HttpResponse::InternalServerError()
+                    .json(ApiResponse::<()>::error(format!("Embedding failed: {}", err)));
             }
-        })
-        .collect();
+        },
+        None => None,
+    };
 
-    scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-    scored.truncate(limit);
+    let update = db::MemoryUpdate {
+        text,
+        tags: Some(revision.tags),
+        source: Some(revision.source),
+        embedding: embedding.map(|e| (e, state.embeddings.model().to_string())),
+    };
+
+    match db::update_memory(&state.db, &collection.0, &id, update, actor.0.as_deref()).await {
+        Ok(Some(memory)) => {
+            HttpResponse::Ok().json(ApiResponse::success(MemoryResponse::from_memory(memory)))
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(ApiResponse::<()>::error("Memory not found"))
+        }
+        Err(err) => {
+            tracing::error!("Failed to revert memory: {}", err);
+            HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error(format!("Failed to revert memory: {}", err)))
+        }
+    }
+}
 
-    HttpResponse::Ok().json(ApiResponse::success(scored))
+async fn list_trash(
+    state: SharedState,
+    collection: CollectionName,
+    query: web::Query<TrashQuery>,
+) -> HttpResponse {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(20).min(100);
+    let state = state.read().await;
+
+    let filter = db::MemoryFilter {
+        collection: Some(collection.0),
+        state: MemoryState::Trashed,
+        ..Default::default()
+    };
+
+    match db::get_memories_paginated(&state.db, page, per_page, &filter).await {
+        Ok(memories) => {
+            let responses: Vec<MemoryResponse> =
+                memories.into_iter().map(MemoryResponse::from_memory).collect();
+            HttpResponse::Ok().json(ApiResponse::success(responses))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to list trash: {}", err))),
+    }
+}
+
+async fn purge_trash(
+    state: SharedState,
+    collection: CollectionName,
+    query: web::Query<PurgeQuery>,
+) -> HttpResponse {
+    let state = state.read().await;
+    let older_than_days = query
+        .older_than_days
+        .unwrap_or(state.config.trash_retention_days);
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(older_than_days.into());
+
+    match db::purge_trash(&state.db, &collection.0, cutoff.into()).await {
+        Ok(purged) => {
+            if purged > 0 {
+                tracing::info!("Purged {} memories from the trash of '{}'", purged, collection.0);
+            }
+            HttpResponse::Ok().json(ApiResponse::success(PurgeResponse {
+                purged,
+                older_than_days,
+            }))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to purge trash: {}", err))),
+    }
+}
+
+async fn search(
+    state: SharedState,
+    collection: CollectionName,
+    query: web::Query<SearchQuery>,
+) -> HttpResponse {
+    if query.q.trim().is_empty() {
+        return HttpResponse::BadRequest()
+            .json(ApiResponse::<()>::error("Query cannot be empty"));
+    }
+
+    let filter = match crate::search::filter_from_query(&query) {
+        Ok(f) => db::MemoryFilter {
+            collection: Some(collection.0),
+            ..f
+        },
+        Err(err) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(err.to_string()));
+        }
+    };
+
+    let state = state.read().await;
+
+    match crate::search::search(&state.db, &state.embeddings, &query, &filter).await {
+        Ok(results) => HttpResponse::Ok().json(ApiResponse::success(results)),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Search failed: {}", err))),
+    }
 }
 
-async fn stats(state: SharedState) -> HttpResponse {
+async fn stats(state: SharedState, collection: CollectionName) -> HttpResponse {
     let state = state.read().await;
+    let collection = collection.0;
 
-    let total = match db::count_memories(&state.db).await {
+    let total = match db::count_memories(&state.db, &collection, MemoryState::Active).await {
         Ok(c) => c,
         Err(err) => {
             return HttpResponse::InternalServerError()
@@ -169,11 +582,26 @@ async fn stats(state: SharedState) -> HttpResponse {
         }
     };
 
-    let tag_counts = db::get_tag_counts(&state.db).await.unwrap_or_default();
-    let source_counts = db::get_source_counts(&state.db).await.unwrap_or_default();
+    let archived = db::count_memories(&state.db, &collection, MemoryState::Archived)
+        .await
+        .unwrap_or_default();
+    let trashed = db::count_memories(&state.db, &collection, MemoryState::Trashed)
+        .await
+        .unwrap_or_default();
+    let tag_counts = db::get_tag_counts(&state.db, &collection).await.unwrap_or_default();
+    let source_counts = db::get_source_counts(&state.db, &collection).await.unwrap_or_default();
+    let stale_embeddings =
+        db::count_stale_embeddings(&state.db, state.embeddings.model(), Some(&collection))
+            .await
+            .unwrap_or_default();
 
     let response = StatsResponse {
+        collection,
         total_memories: total,
+        archived_memories: archived,
+        trashed_memories: trashed,
+        embed_model: state.embeddings.model().to_string(),
+        stale_embeddings,
         tags: tag_counts
             .into_iter()
             .map(|(tag, count)| TagCount { tag, count })
@@ -187,59 +615,467 @@ async fn stats(state: SharedState) -> HttpResponse {
     HttpResponse::Ok().json(ApiResponse::success(response))
 }
 
-async fn get_profile(state: SharedState) -> HttpResponse {
+async fn get_profile(
+    state: SharedState,
+    collection: CollectionName,
+    query: web::Query<ProfileQuery>,
+) -> HttpResponse {
     let state = state.read().await;
 
-    match profile::generate_profile(&state.db, &state.config).await {
-        Ok((profile_text, count)) => HttpResponse::Ok().json(ApiResponse::success(
-            ProfileResponse {
-                profile: profile_text,
-                memory_count: count,
-            },
-        )),
+    let options = match profile::ProfileOptions::from_query(&query, &state.config) {
+        Ok(options) => options,
+        Err(err) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(err.to_string()));
+        }
+    };
+
+    if query.stream {
+        let (db, config) = (state.db.clone(), state.config.clone());
+        return sse::response(move |events| async move {
+            profile::get_profile(&db, &config, &collection.0, &options, Some(&events)).await
+        });
+    }
+
+    match profile::get_profile(&state.db, &state.config, &collection.0, &options, None).await {
+        Ok(profile) => HttpResponse::Ok().json(ApiResponse::success(profile)),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to generate profile: {:#}", err))),
+    }
+}
+
+async fn profile_history(
+    state: SharedState,
+    collection: CollectionName,
+    query: web::Query<ProfileHistoryQuery>,
+) -> HttpResponse {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(20).min(100);
+    let state = state.read().await;
+
+    match db::get_profile_snapshots(&state.db, &collection.0, page, per_page).await {
+        Ok(snapshots) => {
+            let responses: Vec<ProfileSnapshotResponse> = snapshots
+                .into_iter()
+                .map(ProfileSnapshotResponse::from_snapshot)
+                .collect();
+            HttpResponse::Ok().json(ApiResponse::success(responses))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to list profiles: {}", err))),
+    }
+}
+
+async fn profile_snapshot(
+    state: SharedState,
+    collection: CollectionName,
+    path: web::Path<SnapshotPath>,
+    query: web::Query<ProfileDiffQuery>,
+) -> HttpResponse {
+    let id = path.into_inner().id;
+    let state = state.read().await;
+
+    let snapshot = match db::get_profile_snapshot(&state.db, &collection.0, &id).await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()>::error("Profile not found"));
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error(format!("Failed to fetch profile: {}", err)));
+        }
+    };
+
+    let base = match query.against.clone().or_else(|| snapshot.previous.clone()) {
+        Some(base_id) => match db::get_profile_snapshot(&state.db, &collection.0, &base_id).await {
+            Ok(Some(base)) => Some(base),
+            Ok(None) => {
+                return HttpResponse::NotFound()
+                    .json(ApiResponse::<()>::error(format!("Profile {} not found", base_id)));
+            }
+            Err(err) => {
+                return HttpResponse::InternalServerError()
+                    .json(ApiResponse::<()>::error(format!("Failed to fetch profile: {}", err)));
+            }
+        },
+        None => None,
+    };
+
+    HttpResponse::Ok().json(ApiResponse::success(profile::compare(snapshot, base)))
+}
+
+async fn ask_question(
+    state: SharedState,
+    collection: CollectionName,
+    body: web::Json<AskRequest>,
+) -> HttpResponse {
+    let req = body.into_inner();
+
+    if req.question.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Question cannot be empty"));
+    }
+
+    let state = state.read().await;
+
+    if req.stream {
+        let (db, config, embeddings) = (state.db.clone(), state.config.clone(), state.embeddings.clone());
+        return sse::response(move |events| async move {
+            ask::ask(&db, &config, &embeddings, &collection.0, &req, Some(&events)).await
+        });
+    }
+
+    match ask::ask(&state.db, &state.config, &state.embeddings, &collection.0, &req, None).await {
+        Ok(answer) => HttpResponse::Ok().json(ApiResponse::success(answer)),
         Err(err) => HttpResponse::InternalServerError()
-            .json(ApiResponse::<()>::error(format!("Failed to generate profile: {}", err))),
+            .json(ApiResponse::<()>::error(format!("Failed to answer: {:#}", err))),
+    }
+}
+
+/// MCP over streamable HTTP: each POST carries JSON-RPC messages and gets the
+/// replies back as JSON. No event stream is offered, so GET gets a 405.
+async fn mcp(
+    state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
+    access: Access,
+    body: web::Bytes,
+) -> HttpResponse {
+    let server = {
+        let state = state.read().await;
+        McpServer::new(
+            state.db.clone(),
+            state.config.clone(),
+            state.embeddings.clone(),
+            collection.0,
+            actor.0,
+            access,
+        )
+    };
+
+    match server.handle_payload(&body).await {
+        Some(response) => HttpResponse::Ok().json(response),
+        None => HttpResponse::Accepted().finish(),
     }
 }
 
 async fn bulk_create(
     state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
     body: web::Json<BulkCreateRequest>,
 ) -> HttpResponse {
     let req = body.into_inner();
     let state = state.read().await;
-    let mut created = 0;
-    let mut failed = 0;
-    let mut errors = Vec::new();
+    let mut response = BulkResponse::default();
 
+    // Drop empty items before embedding so they don't take a slot in a batch
+    let mut items = Vec::with_capacity(req.memories.len());
     for (i, mem) in req.memories.into_iter().enumerate() {
         if mem.text.trim().is_empty() {
-            failed += 1;
-            errors.push(format!("Item {}: empty text", i));
+            response.failed += 1;
+            response.errors.push(format!("Item {}: empty text", i));
             continue;
         }
+        items.push((i, mem));
+    }
+
+    let texts: Vec<String> = items.iter().map(|(_, mem)| mem.text.clone()).collect();
+    let embeddings = state.embeddings.embed_batch(&texts).await;
 
-        let embedding = match state.embeddings.embed(&mem.text).await {
+    let mut settings = state.config.dedup();
+    settings.policy = req.dedup.unwrap_or(settings.policy);
+    let mut tagger = Tagger::new(&state.db, &state.config, &collection.0);
+
+    // Stored one at a time so later items are checked against earlier ones
+    for ((i, mut mem), embedding) in items.into_iter().zip(embeddings) {
+        let embedding = match embedding {
             Ok(e) => e,
             Err(err) => {
-                failed += 1;
-                errors.push(format!("Item {}: embedding failed: {}", i, err));
+                response.failed += 1;
+                response.errors.push(format!("Item {}: embedding failed: {}", i, err));
                 continue;
             }
         };
 
-        match db::create_memory(&state.db, mem.text, mem.tags, mem.source, embedding).await {
-            Ok(_) => created += 1,
+        mem.auto_tag = mem.auto_tag.or(req.auto_tag);
+        tagger.tag(&mut mem).await;
+
+        let written = dedup::create_memory(
+            &state.db,
+            &collection.0,
+            mem,
+            embedding,
+            state.embeddings.model(),
+            settings,
+            actor.0.as_deref(),
+        )
+        .await;
+
+        let (outcome, existing, similarity) = match written {
+            Ok(Written::Created(_)) => {
+                response.created += 1;
+                continue;
+            }
+            Ok(Written::Merged(memory, similarity)) => {
+                response.merged += 1;
+                (WriteOutcome::Merged, memory, similarity)
+            }
+            Ok(Written::Rejected(memory, similarity)) => {
+                response.rejected += 1;
+                (WriteOutcome::Rejected, memory, similarity)
+            }
             Err(err) => {
-                failed += 1;
-                errors.push(format!("Item {}: {}", i, err));
+                response.failed += 1;
+                response.errors.push(format!("Item {}: {}", i, err));
+                continue;
             }
+        };
+
+        response.duplicates.push(BulkDuplicate {
+            index: i,
+            outcome,
+            duplicate_of: MemoryResponse::from_memory(existing).id,
+            similarity,
+        });
+    }
+
+    HttpResponse::Ok().json(ApiResponse::success(response))
+}
+
+async fn ingest_document(
+    state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
+    body: web::Json<IngestDocumentRequest>,
+) -> HttpResponse {
+    let req = body.into_inner();
+
+    if req.source.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Source cannot be empty"));
+    }
+
+    let state = state.read().await;
+
+    match ingest::ingest_document(
+        &state.db,
+        &state.embeddings,
+        &collection.0,
+        state.config.embed_dim,
+        req,
+        actor.0.as_deref(),
+    )
+    .await
+    {
+        Ok(response) if response.status == DocumentStatus::Created => {
+            HttpResponse::Created().json(ApiResponse::success(response))
+        }
+        Ok(response) => HttpResponse::Ok().json(ApiResponse::success(response)),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to ingest document: {:#}", err))),
+    }
+}
+
+async fn export_memories(
+    state: SharedState,
+    collection: CollectionName,
+    query: web::Query<ExportQuery>,
+) -> HttpResponse {
+    let db = state.read().await.db.clone();
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(transfer::export(db, collection.0, query.include_embeddings))
+}
+
+async fn import_memories(
+    state: SharedState,
+    collection: CollectionName,
+    actor: Actor,
+    query: web::Query<ImportQuery>,
+    payload: web::Payload,
+) -> HttpResponse {
+    let state = state.read().await;
+    let importer = transfer::Importer::new(
+        &state.db,
+        &state.embeddings,
+        &collection.0,
+        state.config.embed_dim,
+        query.overwrite,
+        actor.0.as_deref(),
+    );
+
+    match importer.run(payload).await {
+        Ok(response) => HttpResponse::Ok().json(ApiResponse::success(response)),
+        Err(err) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("{:#}", err))),
+    }
+}
+
+async fn create_collection(
+    state: SharedState,
+    body: web::Json<CreateCollectionRequest>,
+) -> HttpResponse {
+    let req = body.into_inner();
+
+    if !db::is_valid_collection_name(&req.name) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Collection names must be 1-64 letters, digits, '-' or '_'",
+        ));
+    }
+
+    let state = state.read().await;
+
+    match db::create_collection(&state.db, &req.name, req.description).await {
+        Ok(Some(collection)) => HttpResponse::Created().json(ApiResponse::success(
+            CollectionResponse {
+                name: collection.name,
+                description: collection.description,
+                memory_count: 0,
+                created_at: collection.created_at,
+            },
+        )),
+        Ok(None) => HttpResponse::Conflict().json(ApiResponse::<()>::error(format!(
+            "Collection '{}' already exists",
+            req.name
+        ))),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to create collection: {}", err))),
+    }
+}
+
+async fn list_collections(state: SharedState) -> HttpResponse {
+    let state = state.read().await;
+
+    match db::list_collections(&state.db).await {
+        Ok(collections) => {
+            let responses: Vec<CollectionResponse> = collections
+                .into_iter()
+                .map(|(c, memory_count)| CollectionResponse {
+                    name: c.name,
+                    description: c.description,
+                    memory_count,
+                    created_at: c.created_at,
+                })
+                .collect();
+            HttpResponse::Ok().json(ApiResponse::success(responses))
         }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to list collections: {}", err))),
+    }
+}
+
+async fn delete_collection(state: SharedState, path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+
+    if name == db::DEFAULT_COLLECTION {
+        return HttpResponse::BadRequest()
+            .json(ApiResponse::<()>::error("The default collection cannot be deleted"));
     }
 
-    HttpResponse::Ok().json(ApiResponse::success(BulkResponse {
-        created,
-        failed,
-        errors,
-    }))
+    let state = state.read().await;
+
+    match db::delete_collection(&state.db, &name).await {
+        Ok(Some(deleted)) => HttpResponse::Ok().json(ApiResponse::success(format!(
+            "Collection deleted ({} memories removed)",
+            deleted
+        ))),
+        Ok(None) => HttpResponse::NotFound()
+            .json(ApiResponse::<()>::error(format!("Collection '{}' not found", name))),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(ApiResponse::<()>::error(format!("Failed to delete collection: {}", err))),
+    }
+}
+
+async fn start_reembed(
+    state: SharedState,
+    body: Option<web::Json<ReembedRequest>>,
+) -> HttpResponse {
+    let all = body.map(|b| b.all).unwrap_or(false);
+    let state = state.read().await;
+
+    let Some(job) = state.jobs.start("reembed") else {
+        return HttpResponse::Conflict()
+            .json(ApiResponse::<()>::error("A re-embedding job is already running"));
+    };
+
+    tokio::spawn(reembed::run(
+        state.db.clone(),
+        state.embeddings.clone(),
+        state.jobs.clone(),
+        job.id.clone(),
+        state.config.embed_dim,
+        all,
+    ));
+
+    HttpResponse::Accepted().json(ApiResponse::success(job))
+}
+
+async fn start_consolidate(
+    state: SharedState,
+    actor: Actor,
+    body: Option<web::Json<ConsolidateRequest>>,
+) -> HttpResponse {
+    let req = match body {
+        Some(body) => body.into_inner(),
+        None => ConsolidateRequest {
+            collection: db::DEFAULT_COLLECTION.to_string(),
+            threshold: None,
+            max_cluster_size: None,
+            archive: false,
+        },
+    };
+
+    if req.threshold.is_some_and(|t| !(0.0..=1.0).contains(&t)) {
+        return HttpResponse::BadRequest()
+            .json(ApiResponse::<()>::error("threshold must be between 0 and 1"));
+    }
+    if req.max_cluster_size.is_some_and(|n| n < 2) {
+        return HttpResponse::BadRequest()
+            .json(ApiResponse::<()>::error("max_cluster_size must be at least 2"));
+    }
+
+    let state = state.read().await;
+
+    match db::collection_exists(&state.db, &req.collection).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()>::error(format!(
+                "Collection '{}' not found",
+                req.collection
+            )));
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(ApiResponse::<()>::error(format!("{:#}", err)));
+        }
+    }
+
+    let Some(job) = state.jobs.start("consolidate") else {
+        return HttpResponse::Conflict()
+            .json(ApiResponse::<()>::error("A consolidation job is already running"));
+    };
+
+    tokio::spawn(consolidate::run(
+        state.db.clone(),
+        state.config.clone(),
+        state.embeddings.clone(),
+        state.jobs.clone(),
+        job.id.clone(),
+        req,
+        actor.0,
+    ));
+
+    HttpResponse::Accepted().json(ApiResponse::success(job))
+}
+
+async fn list_jobs(state: SharedState) -> HttpResponse {
+    let state = state.read().await;
+    HttpResponse::Ok().json(ApiResponse::success(state.jobs.list()))
+}
+
+async fn get_job(state: SharedState, path: web::Path<String>) -> HttpResponse {
+    let state = state.read().await;
+
+    match state.jobs.get(&path.into_inner()) {
+        Some(job) => HttpResponse::Ok().json(ApiResponse::success(job)),
+        None => HttpResponse::NotFound().json(ApiResponse::<()>::error("Job not found")),
+    }
 }
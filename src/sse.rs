@@ -0,0 +1,114 @@
+use std::future::Future;
+
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use anyhow::Result;
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::chat;
+use crate::config::Config;
+
+/// Events not yet sent to a slow client before generation waits for it
+const BUFFER: usize = 64;
+
+/// A Server-Sent Event from a generation endpoint
+pub enum Event {
+    /// What a long-running generation is busy with
+    Progress(String),
+    /// The next piece of the text being written
+    Token(String),
+    /// The finished result, the same JSON the endpoint returns without
+    /// streaming; always the last event unless generation fails
+    Done(String),
+    Error(String),
+}
+
+impl Event {
+    fn to_bytes(&self) -> Bytes {
+        let (name, data) = match self {
+            Self::Progress(message) => ("progress", json!({ "message": message }).to_string()),
+            Self::Token(text) => ("token", json!({ "text": text }).to_string()),
+            Self::Done(result) => ("done", result.clone()),
+            Self::Error(error) => ("error", json!({ "error": error }).to_string()),
+        };
+        // JSON escapes newlines, so the data always fits on one line
+        Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+    }
+}
+
+/// Where a generation sends its events
+pub type Events = mpsc::Sender<Event>;
+
+/// Send an event, failing once the client has gone away so generation can
+/// stop early.
+pub async fn send(events: &Events, event: Event) -> Result<()> {
+    events
+        .send(event)
+        .await
+        .map_err(|_| anyhow::anyhow!("Client disconnected"))
+}
+
+/// Report what a generation is busy with to `events`, if there are any.
+pub async fn progress(events: Option<&Events>, message: String) -> Result<()> {
+    match events {
+        Some(events) => send(events, Event::Progress(message)).await,
+        None => Ok(()),
+    }
+}
+
+/// An event stream response for `generate`, which runs on its own task and
+/// reports through the events it's given. Its result becomes the `done`
+/// event, or an `error` event if it fails.
+pub fn response<F, Fut, T>(generate: F) -> HttpResponse
+where
+    F: FnOnce(Events) -> Fut,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+    T: Serialize,
+{
+    let (tx, rx) = mpsc::channel(BUFFER);
+    let work = generate(tx.clone());
+
+    tokio::spawn(async move {
+        let event = match work.await.and_then(|result| Ok(serde_json::to_string(&result)?)) {
+            Ok(result) => Event::Done(result),
+            // Nobody is left to tell
+            Err(_) if tx.is_closed() => return,
+            Err(err) => {
+                tracing::error!("Streaming generation failed: {:#}", err);
+                Event::Error(format!("{:#}", err))
+            }
+        };
+        let _ = tx.send(event).await;
+    });
+
+    let body = futures::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        Some((Ok::<_, actix_web::Error>(event.to_bytes()), rx))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(body)
+}
+
+/// Complete `prompt` with the chat model, sending the text to `events` as
+/// it's written when there are any. Returns the whole completion.
+pub async fn generate(config: &Config, prompt: String, events: Option<&Events>) -> Result<String> {
+    let Some(events) = events else {
+        return chat::generate(config, prompt).await;
+    };
+
+    let mut stream = chat::generate_stream(config, prompt).await?;
+    let mut text = String::new();
+    while let Some(token) = stream.next().await {
+        let token = token?;
+        text.push_str(&token);
+        send(events, Event::Token(token)).await?;
+    }
+    Ok(text.trim().to_string())
+}
@@ -0,0 +1,259 @@
+use actix_web::web::{self, Bytes};
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use futures::{Stream, StreamExt};
+use surrealdb::sql::Datetime;
+
+use crate::db::{self, Db, ImportedMemory};
+use crate::embeddings::EmbeddingClient;
+use crate::models::{ExportRecord, ImportResponse};
+
+/// Memories read from the database per page while exporting
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Longest line accepted on import, which is generous for a memory with a
+/// large embedding
+const MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+
+/// A collection as JSONL, one `ExportRecord` per line, oldest memory first.
+/// Memories are read a page at a time so large collections aren't held in
+/// memory.
+pub fn export(
+    db: Db,
+    collection: String,
+    include_embeddings: bool,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    futures::stream::unfold(Some(0), move |offset| {
+        let db = db.clone();
+        let collection = collection.clone();
+        async move {
+            let offset = offset?;
+            let page = match db::get_memories_page(&db, &collection, offset, EXPORT_PAGE_SIZE).await {
+                Ok(page) => page,
+                Err(err) => {
+                    tracing::error!("Export failed: {:#}", err);
+                    return Some((Err(actix_web::error::ErrorInternalServerError(err)), None));
+                }
+            };
+            if page.is_empty() {
+                return None;
+            }
+
+            let next = (page.len() == EXPORT_PAGE_SIZE).then_some(offset + page.len());
+            let mut lines = Vec::new();
+            for memory in page {
+                let record = ExportRecord::from_memory(memory, include_embeddings);
+                if let Err(err) = serde_json::to_writer(&mut lines, &record) {
+                    return Some((Err(actix_web::error::ErrorInternalServerError(err)), None));
+                }
+                lines.push(b'\n');
+            }
+            Some((Ok(Bytes::from(lines)), next))
+        }
+    })
+}
+
+/// A parsed line waiting to be stored
+struct Pending {
+    line: usize,
+    memory: ImportedMemory,
+    /// The line had no usable embedding, so one must be computed
+    reembed: bool,
+}
+
+/// Reads JSONL records from a request body and stores them in batches.
+pub struct Importer<'a> {
+    db: &'a Db,
+    embeddings: &'a EmbeddingClient,
+    collection: &'a str,
+    embed_dim: usize,
+    overwrite: bool,
+    actor: Option<&'a str>,
+    pending: Vec<Pending>,
+    response: ImportResponse,
+}
+
+impl<'a> Importer<'a> {
+    pub fn new(
+        db: &'a Db,
+        embeddings: &'a EmbeddingClient,
+        collection: &'a str,
+        embed_dim: usize,
+        overwrite: bool,
+        actor: Option<&'a str>,
+    ) -> Self {
+        Self {
+            db,
+            embeddings,
+            collection,
+            embed_dim,
+            overwrite,
+            actor,
+            pending: Vec::new(),
+            response: ImportResponse::default(),
+        }
+    }
+
+    /// Import every line of `payload`. Malformed lines are counted as failed
+    /// and reported; an error is only returned if the body can't be read.
+    ///
+    /// Embeddings in the file are kept when they come from the configured
+    /// model and have the configured dimension; other lines are embedded
+    /// again.
+    pub async fn run(mut self, mut payload: web::Payload) -> Result<ImportResponse> {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut line = 0;
+
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.context("Failed to read request body")?;
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let rest = buffer.split_off(end + 1);
+                line += 1;
+                self.push_line(line, &buffer[..end]).await;
+                buffer = rest;
+            }
+
+            if buffer.len() > MAX_LINE_BYTES {
+                anyhow::bail!("Line {} is longer than {} bytes", line + 1, MAX_LINE_BYTES);
+            }
+        }
+
+        if !buffer.is_empty() {
+            self.push_line(line + 1, &buffer).await;
+        }
+        self.flush().await;
+        Ok(self.response)
+    }
+
+    async fn push_line(&mut self, line: usize, bytes: &[u8]) {
+        if bytes.iter().all(u8::is_ascii_whitespace) {
+            return;
+        }
+
+        match self.parse(line, bytes) {
+            Ok(pending) => self.pending.push(pending),
+            Err(err) => self.fail(line, err),
+        }
+
+        // Hand the embedding client enough work to keep every request slot busy
+        if self.pending.len() >= self.embeddings.batch_size() * self.embeddings.concurrency() {
+            self.flush().await;
+        }
+    }
+
+    fn parse(&self, line: usize, bytes: &[u8]) -> Result<Pending> {
+        let record: ExportRecord = serde_json::from_slice(bytes).context("Invalid JSON")?;
+        if record.text.trim().is_empty() {
+            anyhow::bail!("empty text");
+        }
+        if record.id.as_deref().is_some_and(str::is_empty) {
+            anyhow::bail!("empty id");
+        }
+
+        let model = self.embeddings.model();
+        let embedding = record
+            .embedding
+            .filter(|e| e.len() == self.embed_dim && record.embed_model.as_deref() == Some(model));
+
+        Ok(Pending {
+            line,
+            reembed: embedding.is_none(),
+            memory: ImportedMemory {
+                id: record.id,
+                text: record.text,
+                tags: record.tags,
+                source: record.source,
+                created_at: parse_timestamp("created_at", record.created_at.as_deref())?,
+                updated_at: parse_timestamp("updated_at", record.updated_at.as_deref())?,
+                archived_at: parse_timestamp("archived_at", record.archived_at.as_deref())?,
+                embedding: embedding.unwrap_or_default(),
+                embed_model: model.to_string(),
+            },
+        })
+    }
+
+    async fn flush(&mut self) {
+        let mut pending = std::mem::take(&mut self.pending);
+
+        // Memories already in the collection are kept, so their lines needn't
+        // be embedded
+        if !self.overwrite {
+            let ids: Vec<String> = pending.iter().filter_map(|p| p.memory.id.clone()).collect();
+            if !ids.is_empty() {
+                match db::existing_memory_ids(self.db, self.collection, &ids).await {
+                    Ok(existing) => {
+                        let skipped = &mut self.response.skipped;
+                        pending.retain(|p| {
+                            let exists = p.memory.id.as_ref().is_some_and(|id| existing.contains(id));
+                            if exists {
+                                *skipped += 1;
+                            }
+                            !exists
+                        });
+                    }
+                    // `import_memory` still skips them, after they're embedded
+                    Err(err) => tracing::warn!("Failed to look up existing memories: {:#}", err),
+                }
+            }
+        }
+
+        let texts: Vec<String> = pending
+            .iter()
+            .filter(|p| p.reembed)
+            .map(|p| p.memory.text.clone())
+            .collect();
+        let mut embeddings = self.embeddings.embed_batch(&texts).await.into_iter();
+
+        for p in pending.iter_mut().filter(|p| p.reembed) {
+            match embeddings.next() {
+                Some(Ok(e)) if e.len() != self.embed_dim => self.fail(
+                    p.line,
+                    anyhow::anyhow!(
+                        "model returned {} dimensions but MEMORAI_EMBED_DIM is {}",
+                        e.len(),
+                        self.embed_dim
+                    ),
+                ),
+                Some(Ok(e)) => p.memory.embedding = e,
+                Some(Err(err)) => self.fail(p.line, err.context("embedding failed")),
+                None => self.fail(p.line, anyhow::anyhow!("embedding failed")),
+            }
+        }
+
+        for p in pending {
+            // Lines that failed to embed were left without a vector
+            if p.memory.embedding.is_empty() {
+                continue;
+            }
+
+            let reembed = p.reembed;
+            match db::import_memory(self.db, self.collection, p.memory, self.overwrite, self.actor).await {
+                Ok(true) => {
+                    self.response.imported += 1;
+                    if reembed {
+                        self.response.reembedded += 1;
+                    }
+                }
+                Ok(false) => self.response.skipped += 1,
+                Err(err) => self.fail(p.line, err),
+            }
+        }
+    }
+
+    fn fail(&mut self, line: usize, err: anyhow::Error) {
+        self.response.failed += 1;
+        self.response.errors.push(format!("Line {}: {:#}", line, err));
+    }
+}
+
+fn parse_timestamp(name: &str, value: Option<&str>) -> Result<Option<Datetime>> {
+    value
+        .map(|v| {
+            DateTime::parse_from_rfc3339(v)
+                .map(|dt| dt.to_utc().into())
+                .with_context(|| format!("Invalid {}: expected an RFC 3339 timestamp", name))
+        })
+        .transpose()
+}